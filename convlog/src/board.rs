@@ -0,0 +1,526 @@
+//! Table state replayed from mjai events.
+
+use crate::{Event, Tile, tile_set_eq};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum BoardError {
+    #[error("{tile} not found in the hand of actor {actor}")]
+    TileNotInHand { actor: u8, tile: Tile },
+
+    #[error("previous pon not found for kakan of {tile} by actor {actor}")]
+    PonNotFound { actor: u8, tile: Tile },
+}
+
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    #[error("cannot seek to {pos} out of {len} events")]
+    OutOfRange { pos: usize, len: usize },
+
+    #[error("at event #{index}")]
+    Board {
+        index: usize,
+        #[source]
+        source: BoardError,
+    },
+}
+
+/// Everything on the table, as known from the events applied so far.
+///
+/// Hands may contain unknown tiles, as in the events sent to an engine. A
+/// tile taken from such a hand but not found in it is taken from the unknown
+/// ones instead.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Board {
+    pub bakaze: Option<Tile>,
+    /// Counts from 1.
+    pub kyoku: u8,
+    pub honba: u8,
+    pub kyotaku: u8,
+    pub oya: u8,
+    pub scores: [i32; 4],
+    pub dora_markers: Vec<Tile>,
+    /// Number of draws left in the wall.
+    pub tiles_left: u8,
+    /// In absolute seat order.
+    pub seats: [Seat; 4],
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Seat {
+    /// Sorted, not including `tsumo`. Left out when serialized if entirely
+    /// unknown.
+    #[serde(default, skip_serializing_if = "all_unknown")]
+    pub tehai: Vec<Tile>,
+    /// The drawn tile, until the next discard or call. Left out when
+    /// serialized if absent or unknown.
+    #[serde(default, skip_serializing_if = "is_none_or_unknown")]
+    pub tsumo: Option<Tile>,
+    pub river: Vec<Discard>,
+    pub fuuros: Vec<Fuuro>,
+    /// Index into `river` of the discard declaring riichi.
+    pub riichi_at: Option<usize>,
+    pub riichi_accepted: bool,
+    /// Tiles discarded by the others since the riichi, on which the seat can
+    /// no longer ron.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub passed_after_riichi: Vec<Tile>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Discard {
+    pub pai: Tile,
+    pub tsumogiri: bool,
+    /// Called away by another player, shown in the meld instead.
+    pub called: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+#[serde(rename_all = "snake_case")]
+pub enum Fuuro {
+    Chi {
+        target: u8,
+        pai: Tile,
+        consumed: [Tile; 2],
+    },
+    Pon {
+        target: u8,
+        pai: Tile,
+        consumed: [Tile; 2],
+    },
+    Daiminkan {
+        target: u8,
+        pai: Tile,
+        consumed: [Tile; 3],
+    },
+    Kakan {
+        pai: Tile,
+        previous_pon_target: u8,
+        previous_pon_pai: Tile,
+        consumed: [Tile; 2],
+    },
+    Ankan {
+        consumed: [Tile; 4],
+    },
+}
+
+fn all_unknown(tiles: &[Tile]) -> bool {
+    tiles.iter().all(|t| t.is_unknown())
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)] // required by serde
+fn is_none_or_unknown(tile: &Option<Tile>) -> bool {
+    tile.is_none_or(|t| t.is_unknown())
+}
+
+impl Seat {
+    /// Whether every tile in hand is known.
+    #[must_use]
+    pub fn is_hand_known(&self) -> bool {
+        !self.tehai.iter().chain(&self.tsumo).any(|t| t.is_unknown())
+    }
+
+    /// The hand with the drawn tile, as deaka'd counts of the 34 kinds, in
+    /// which unknown tiles are left out.
+    #[must_use]
+    pub fn counts(&self) -> [u8; 34] {
+        let mut counts = [0; 34];
+        for t in self.tehai.iter().chain(&self.tsumo) {
+            if !t.is_unknown() {
+                counts[t.deaka().as_usize()] += 1;
+            }
+        }
+        counts
+    }
+
+    fn merge_tsumo(&mut self) {
+        if let Some(tile) = self.tsumo.take() {
+            let idx = self.tehai.partition_point(|&t| t < tile);
+            self.tehai.insert(idx, tile);
+        }
+    }
+
+    fn take(&mut self, actor: u8, tile: Tile) -> Result<(), BoardError> {
+        self.merge_tsumo();
+        let idx = self
+            .tehai
+            .iter()
+            .position(|&t| t == tile)
+            .or_else(|| self.tehai.iter().position(|t| t.is_unknown()))
+            .ok_or(BoardError::TileNotInHand { actor, tile })?;
+        self.tehai.remove(idx);
+        Ok(())
+    }
+}
+
+impl Board {
+    /// Applies an event. Events with nothing to do with the table are no-op.
+    pub fn apply(&mut self, event: &Event) -> Result<(), BoardError> {
+        match *event {
+            Event::StartKyoku {
+                bakaze,
+                dora_marker,
+                kyoku,
+                honba,
+                kyotaku,
+                oya,
+                scores,
+                ref tehais,
+            } => {
+                *self = Self {
+                    bakaze: Some(bakaze),
+                    kyoku,
+                    honba,
+                    kyotaku,
+                    oya,
+                    scores,
+                    dora_markers: vec![dora_marker],
+                    tiles_left: 70,
+                    seats: Default::default(),
+                };
+                for (seat, tehai) in self.seats.iter_mut().zip(tehais) {
+                    seat.tehai = tehai.to_vec();
+                    seat.tehai.sort_unstable();
+                }
+            }
+
+            Event::Tsumo { actor, pai } => {
+                self.tiles_left = self.tiles_left.saturating_sub(1);
+                let seat = &mut self.seats[actor as usize];
+                seat.merge_tsumo();
+                seat.tsumo = Some(pai);
+            }
+
+            Event::Dahai {
+                actor,
+                pai,
+                tsumogiri,
+            } => {
+                let seat = &mut self.seats[actor as usize];
+                match seat.tsumo {
+                    Some(t) if tsumogiri && (t == pai || t.is_unknown()) => seat.tsumo = None,
+                    _ => seat.take(actor, pai)?,
+                }
+                seat.river.push(Discard {
+                    pai,
+                    tsumogiri,
+                    called: false,
+                });
+                for (idx, s) in self.seats.iter_mut().enumerate() {
+                    if idx != actor as usize && s.riichi_at.is_some() {
+                        s.passed_after_riichi.push(pai);
+                    }
+                }
+            }
+
+            Event::Chi {
+                actor,
+                target,
+                pai,
+                consumed,
+            } => {
+                self.take_multiple(actor, &consumed)?;
+                self.mark_called(target);
+                self.seats[actor as usize].fuuros.push(Fuuro::Chi {
+                    target,
+                    pai,
+                    consumed,
+                });
+            }
+
+            Event::Pon {
+                actor,
+                target,
+                pai,
+                consumed,
+            } => {
+                self.take_multiple(actor, &consumed)?;
+                self.mark_called(target);
+                self.seats[actor as usize].fuuros.push(Fuuro::Pon {
+                    target,
+                    pai,
+                    consumed,
+                });
+            }
+
+            Event::Daiminkan {
+                actor,
+                target,
+                pai,
+                consumed,
+            } => {
+                self.take_multiple(actor, &consumed)?;
+                self.mark_called(target);
+                self.seats[actor as usize].fuuros.push(Fuuro::Daiminkan {
+                    target,
+                    pai,
+                    consumed,
+                });
+            }
+
+            Event::Kakan {
+                actor,
+                pai,
+                consumed,
+            } => {
+                let seat = &mut self.seats[actor as usize];
+                seat.take(actor, pai)?;
+                let (idx, previous_pon_target, previous_pon_pai, previous_pon_consumed) = seat
+                    .fuuros
+                    .iter()
+                    .enumerate()
+                    .find_map(|(idx, f)| match *f {
+                        Fuuro::Pon {
+                            target,
+                            pai,
+                            consumed: pon_consumed,
+                        } if tile_set_eq(
+                            &[pai, pon_consumed[0], pon_consumed[1]],
+                            &consumed,
+                            false,
+                        ) =>
+                        {
+                            Some((idx, target, pai, pon_consumed))
+                        }
+                        _ => None,
+                    })
+                    .ok_or(BoardError::PonNotFound { actor, tile: pai })?;
+                seat.fuuros[idx] = Fuuro::Kakan {
+                    pai,
+                    previous_pon_target,
+                    previous_pon_pai,
+                    consumed: previous_pon_consumed,
+                };
+            }
+
+            Event::Ankan { actor, consumed } => {
+                self.take_multiple(actor, &consumed)?;
+                self.seats[actor as usize]
+                    .fuuros
+                    .push(Fuuro::Ankan { consumed });
+            }
+
+            Event::Dora { dora_marker } => self.dora_markers.push(dora_marker),
+
+            Event::Reach { actor } => {
+                let seat = &mut self.seats[actor as usize];
+                seat.riichi_at = Some(seat.river.len());
+            }
+
+            Event::ReachAccepted { actor } => {
+                self.seats[actor as usize].riichi_accepted = true;
+                self.scores[actor as usize] -= 1000;
+                self.kyotaku += 1;
+            }
+
+            Event::Hora { deltas, .. } | Event::Ryukyoku { deltas } => {
+                if let Some(deltas) = deltas {
+                    for (score, delta) in self.scores.iter_mut().zip(deltas) {
+                        *score += delta;
+                    }
+                }
+                if matches!(event, Event::Hora { .. }) {
+                    self.kyotaku = 0;
+                }
+            }
+
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn take_multiple(&mut self, actor: u8, tiles: &[Tile]) -> Result<(), BoardError> {
+        let seat = &mut self.seats[actor as usize];
+        for &tile in tiles {
+            seat.take(actor, tile)?;
+        }
+        Ok(())
+    }
+
+    fn mark_called(&mut self, target: u8) {
+        if let Some(discard) = self.seats[target as usize].river.last_mut() {
+            discard.called = true;
+        }
+    }
+
+    /// Number of each kind of tile visible on the table, in all rivers and
+    /// melds and the dora indicators, not including any hand.
+    #[must_use]
+    pub fn visible_counts(&self) -> [u8; 34] {
+        let mut counts = [0; 34];
+        let mut add = |t: &Tile| counts[t.deaka().as_usize()] += 1;
+        self.dora_markers.iter().for_each(&mut add);
+        for seat in &self.seats {
+            // called ones are counted in the melds
+            seat.river
+                .iter()
+                .filter(|d| !d.called)
+                .for_each(|d| add(&d.pai));
+            for fuuro in &seat.fuuros {
+                match fuuro {
+                    Fuuro::Chi { pai, consumed, .. } | Fuuro::Pon { pai, consumed, .. } => {
+                        add(pai);
+                        consumed.iter().for_each(&mut add);
+                    }
+                    Fuuro::Daiminkan { pai, consumed, .. } => {
+                        add(pai);
+                        consumed.iter().for_each(&mut add);
+                    }
+                    Fuuro::Kakan {
+                        pai,
+                        previous_pon_pai,
+                        consumed,
+                        ..
+                    } => {
+                        add(pai);
+                        add(previous_pon_pai);
+                        consumed.iter().for_each(&mut add);
+                    }
+                    Fuuro::Ankan { consumed } => consumed.iter().for_each(&mut add),
+                }
+            }
+        }
+        counts
+    }
+}
+
+/// Replays a sequence of events, which can be seeked back and forth.
+///
+/// The board at the start of every kyoku passed is kept, so that seeking back
+/// only replays the events from the start of the kyoku.
+#[derive(Debug, Clone)]
+pub struct Replay<'a> {
+    events: &'a [Event],
+    board: Board,
+    /// Number of events applied.
+    pos: usize,
+    /// Sorted by the number of events applied.
+    checkpoints: Vec<(usize, Board)>,
+}
+
+impl<'a> Replay<'a> {
+    #[must_use]
+    pub fn new(events: &'a [Event]) -> Self {
+        Self {
+            events,
+            board: Board::default(),
+            pos: 0,
+            checkpoints: vec![],
+        }
+    }
+
+    /// Makes the board the one after applying the first `pos` events.
+    pub fn seek(&mut self, pos: usize) -> Result<&Board, ReplayError> {
+        let len = self.events.len();
+        if pos > len {
+            return Err(ReplayError::OutOfRange { pos, len });
+        }
+        if pos < self.pos {
+            let (p, board) = self
+                .checkpoints
+                .iter()
+                .rev()
+                .find(|(p, _)| *p <= pos)
+                .cloned()
+                .unwrap_or_default();
+            self.pos = p;
+            self.board = board;
+        }
+        while self.pos < pos {
+            let index = self.pos;
+            let event = &self.events[index];
+            self.board
+                .apply(event)
+                .map_err(|source| ReplayError::Board { index, source })?;
+            self.pos += 1;
+            if matches!(event, Event::StartKyoku { .. })
+                && self.checkpoints.last().is_none_or(|(p, _)| *p < self.pos)
+            {
+                self.checkpoints.push((self.pos, self.board.clone()));
+            }
+        }
+        Ok(&self.board)
+    }
+
+    /// Applies the next event, returning `None` at the end.
+    pub fn step(&mut self) -> Option<Result<&Board, ReplayError>> {
+        (self.pos < self.events.len()).then(|| self.seek(self.pos + 1))
+    }
+
+    #[must_use]
+    pub const fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Number of events applied.
+    #[must_use]
+    pub const fn position(&self) -> usize {
+        self.pos
+    }
+
+    #[must_use]
+    pub fn snapshot(&self) -> Board {
+        self.board.clone()
+    }
+}
+
+impl Board {
+    /// Replays all the events, returning the board at the end.
+    pub fn from_events(events: &[Event]) -> Result<Self, ReplayError> {
+        let mut replay = Replay::new(events);
+        replay.seek(events.len())?;
+        Ok(replay.board)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::t;
+
+    #[test]
+    fn unknown_tiles() {
+        let mut board = Board::default();
+        let tehais = [[t!(?); 13], [t!(1m); 13], [t!(?); 13], [t!(?); 13]];
+        board
+            .apply(&Event::StartKyoku {
+                bakaze: t!(E),
+                dora_marker: t!(1p),
+                kyoku: 1,
+                honba: 0,
+                kyotaku: 0,
+                oya: 0,
+                scores: [25000; 4],
+                tehais,
+            })
+            .unwrap();
+        board
+            .apply(&Event::Tsumo {
+                actor: 0,
+                pai: t!(?),
+            })
+            .unwrap();
+        board
+            .apply(&Event::Dahai {
+                actor: 0,
+                pai: t!(E),
+                tsumogiri: false,
+            })
+            .unwrap();
+        assert_eq!(board.seats[0].tehai.len(), 13);
+        assert!(!board.seats[0].is_hand_known());
+        assert_eq!(board.tiles_left, 69);
+
+        let err = board
+            .apply(&Event::Dahai {
+                actor: 1,
+                pai: t!(2m),
+                tsumogiri: false,
+            })
+            .unwrap_err();
+        assert!(matches!(err, BoardError::TileNotInHand { actor: 1, .. }));
+    }
+}
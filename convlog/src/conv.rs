@@ -155,19 +155,18 @@ fn tenhou_kyoku_to_mjai_events(kyoku: &Kyoku) -> Result<Vec<Event>> {
             )?;
             take_idxs[actor] += 1;
 
-            if let Some((target, pai)) = take.naki_info() {
-                if pai != last_discard
-                    || last_actor.is_some_and(|a| a != target || a == actor as u8)
-                {
-                    return Err(ConvertError::UnexpectedNaki {
-                        action: take.clone(),
-                        last_discard,
-                        last_actor,
-                        kyoku: kyoku.meta.kyoku_num,
-                        honba: kyoku.meta.honba,
-                        actor: actor as u8,
-                    });
-                }
+            if let Some((target, pai)) = take.naki_info()
+                && (pai != last_discard
+                    || last_actor.is_some_and(|a| a != target || a == actor as u8))
+            {
+                return Err(ConvertError::UnexpectedNaki {
+                    action: take.clone(),
+                    last_discard,
+                    last_actor,
+                    kyoku: kyoku.meta.kyoku_num,
+                    honba: kyoku.meta.honba,
+                    actor: actor as u8,
+                });
             }
 
             // If a reach event was emitted before, set it as accepted now.
@@ -334,12 +333,12 @@ fn tenhou_kyoku_to_mjai_events(kyoku: &Kyoku) -> Result<Vec<Event>> {
                 // First pass, filter the naki that takes the specific tile from the
                 // specific target.
                 .filter_map(|a| {
-                    if let Some(take) = take_events[a].get(take_idxs[a]) {
-                        if let Some((target, pai)) = take.naki_info() {
-                            if target == (actor as u8) && pai == last_discard {
-                                return Some((a, take.naki_to_ord()));
-                            }
-                        }
+                    if let Some(take) = take_events[a].get(take_idxs[a])
+                        && let Some((target, pai)) = take.naki_info()
+                        && target == (actor as u8)
+                        && pai == last_discard
+                    {
+                        return Some((a, take.naki_to_ord()));
                     }
 
                     None
@@ -74,13 +74,12 @@
     clippy::string_add,
     clippy::string_add_assign,
     clippy::string_lit_as_bytes,
-    clippy::string_to_string,
     clippy::suboptimal_flops,
     clippy::suspicious_to_owned,
     clippy::trait_duplication_in_bounds,
     clippy::trivially_copy_pass_by_ref,
     clippy::type_repetition_in_bounds,
-    clippy::unchecked_duration_subtraction,
+    clippy::unchecked_time_subtraction,
     clippy::undocumented_unsafe_blocks,
     clippy::unicode_not_nfc,
     clippy::uninlined_format_args,
@@ -96,14 +95,17 @@
     clippy::useless_let_if_seq
 )]
 
+mod board;
 mod conv;
 mod kyoku_filter;
 mod macros;
 mod mjai;
 mod tile;
 
+pub mod shanten;
 pub mod tenhou;
 
+pub use board::{Board, BoardError, Discard, Fuuro, Replay, ReplayError, Seat};
 pub use conv::ConvertError;
 pub use conv::tenhou_to_mjai;
 pub use kyoku_filter::KyokuFilter;
@@ -0,0 +1,177 @@
+//! Shanten number calculation.
+//!
+//! The API follows Mortal:/libriichi/src/algo/shanten.rs, while the
+//! implementation is a plain search, which is slower but needs no tables.
+//!
+//! `tehai` is always a 34-slot count array of deaka'd tiles and `len_div3` is
+//! the number of tiles in hand divided by 3, that is, 4 minus the number of
+//! melds. A hand of `len_div3 * 3 + 2` tiles is complete when the result is
+//! -1.
+//!
+//! This is public API, used by both mjai-reviewer and fake-engine, so the
+//! results are tested against every winning hand in testdata as well.
+
+/// Returns the minimum of all forms. Chiitoitsu and kokushi are only
+/// considered for closed hands.
+///
+/// ```
+/// use convlog::{Tile, shanten};
+///
+/// let mut tehai = [0; 34];
+/// for t in "1m 2m 3m 4p 5p 6p 7s 8s 9s E E E S".split(' ') {
+///     tehai[t.parse::<Tile>().unwrap().as_usize()] += 1;
+/// }
+/// assert_eq!(shanten::calc_all(&tehai, 4), 0);
+/// ```
+#[must_use]
+pub fn calc_all(tehai: &[u8; 34], len_div3: u8) -> i8 {
+    let mut shanten = calc_normal(tehai, len_div3);
+    if shanten <= 0 || len_div3 < 4 {
+        return shanten;
+    }
+    shanten = shanten.min(calc_chitoi(tehai));
+    if shanten > 0 {
+        shanten = shanten.min(calc_kokushi(tehai));
+    }
+    shanten
+}
+
+/// Only the form of 4 sets and a pair, or fewer sets with melds.
+#[must_use]
+pub fn calc_normal(tehai: &[u8; 34], len_div3: u8) -> i8 {
+    let mut tehai = *tehai;
+    let mut search = Search {
+        len_div3: len_div3 as i8,
+        best: len_div3 as i8 * 2,
+    };
+    search.run(&mut tehai, 0, 0, 0, false);
+    search.best
+}
+
+/// Only chiitoitsu, for a closed hand of 13 or 14 tiles.
+#[must_use]
+pub fn calc_chitoi(tehai: &[u8; 34]) -> i8 {
+    let pairs = tehai.iter().filter(|&&c| c >= 2).count() as i8;
+    let kinds = tehai.iter().filter(|&&c| c > 0).count() as i8;
+    6 - pairs + (7 - kinds).max(0)
+}
+
+/// Only kokushi musou, for a closed hand of 13 or 14 tiles.
+#[must_use]
+pub fn calc_kokushi(tehai: &[u8; 34]) -> i8 {
+    const YAOKYUU: [usize; 13] = [0, 8, 9, 17, 18, 26, 27, 28, 29, 30, 31, 32, 33];
+    let kinds = YAOKYUU.iter().filter(|&&i| tehai[i] > 0).count() as i8;
+    let has_pair = YAOKYUU.iter().any(|&i| tehai[i] >= 2);
+    13 - kinds - i8::from(has_pair)
+}
+
+struct Search {
+    len_div3: i8,
+    best: i8,
+}
+
+impl Search {
+    fn run(&mut self, tehai: &mut [u8; 34], start: usize, mentsu: i8, taatsu: i8, pair: bool) {
+        let Some(i) = (start..34).find(|&i| tehai[i] > 0) else {
+            let taatsu = taatsu.min(self.len_div3 - mentsu);
+            let shanten = self.len_div3 * 2 - mentsu * 2 - taatsu - i8::from(pair);
+            self.best = self.best.min(shanten);
+            return;
+        };
+        if self.best == -1 {
+            return;
+        }
+        let suited = i < 27;
+        let num = i % 9;
+
+        if tehai[i] >= 3 {
+            tehai[i] -= 3;
+            self.run(tehai, i, mentsu + 1, taatsu, pair);
+            tehai[i] += 3;
+        }
+        if suited && num <= 6 && tehai[i + 1] > 0 && tehai[i + 2] > 0 {
+            take(tehai, &[i, i + 1, i + 2]);
+            self.run(tehai, i, mentsu + 1, taatsu, pair);
+            put(tehai, &[i, i + 1, i + 2]);
+        }
+        if tehai[i] >= 2 {
+            tehai[i] -= 2;
+            if !pair {
+                self.run(tehai, i, mentsu, taatsu, true);
+            }
+            self.run(tehai, i, mentsu, taatsu + 1, pair);
+            tehai[i] += 2;
+        }
+        if suited && num <= 7 && tehai[i + 1] > 0 {
+            take(tehai, &[i, i + 1]);
+            self.run(tehai, i, mentsu, taatsu + 1, pair);
+            put(tehai, &[i, i + 1]);
+        }
+        if suited && num <= 6 && tehai[i + 2] > 0 {
+            take(tehai, &[i, i + 2]);
+            self.run(tehai, i, mentsu, taatsu + 1, pair);
+            put(tehai, &[i, i + 2]);
+        }
+
+        // leave it isolated
+        let count = tehai[i];
+        tehai[i] = 0;
+        self.run(tehai, i + 1, mentsu, taatsu, pair);
+        tehai[i] = count;
+    }
+}
+
+fn take(tehai: &mut [u8; 34], ids: &[usize]) {
+    for &id in ids {
+        tehai[id] -= 1;
+    }
+}
+
+fn put(tehai: &mut [u8; 34], ids: &[usize]) {
+    for &id in ids {
+        tehai[id] += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Tile;
+
+    fn count(tiles: &str) -> [u8; 34] {
+        let mut tehai = [0; 34];
+        for t in tiles.split_whitespace() {
+            let tile: Tile = t.parse().unwrap();
+            tehai[tile.deaka().as_usize()] += 1;
+        }
+        tehai
+    }
+
+    #[test]
+    fn shanten() {
+        let tehai = count("1m 2m 3m 4p 5p 6p 7s 8s 9s E E E S S");
+        assert_eq!(calc_all(&tehai, 4), -1);
+
+        let tehai = count("1m 2m 3m 4p 5p 6p 7s 8s 9s E E E S");
+        assert_eq!(calc_all(&tehai, 4), 0);
+
+        let tehai = count("1m 2m 4m 4p 5p 7p 7s 8s 9s E E W S");
+        assert_eq!(calc_all(&tehai, 4), 3);
+
+        let tehai = count("1m 1m 3m 3m 5p 5p 7p 7p 9s 9s E E S");
+        assert_eq!(calc_all(&tehai, 4), 0);
+        assert_eq!(calc_normal(&tehai, 4), 3);
+
+        let tehai = count("1m 9m 1p 9p 1s 9s E S W N P F C");
+        assert_eq!(calc_all(&tehai, 4), 0);
+
+        let tehai = count("1m 9m 1p 9p 1s 9s E S W N P F C C");
+        assert_eq!(calc_all(&tehai, 4), -1);
+
+        // with three melds
+        let tehai = count("2m 3m 4m 6s 6s");
+        assert_eq!(calc_all(&tehai, 1), -1);
+        let tehai = count("2m 3m 6s 6s N");
+        assert_eq!(calc_all(&tehai, 1), 0);
+    }
+}
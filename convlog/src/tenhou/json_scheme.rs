@@ -111,14 +111,13 @@ impl RawLog {
 
     #[inline]
     pub fn hide_names(&mut self) {
-        self.names
-            .iter_mut()
-            .zip('A'..='D')
-            .for_each(|(name, alias)| {
-                name.clear();
-                name.push(alias);
-                name.push_str("さん");
-            });
+        self.names = Self::anonymous_names();
+    }
+
+    /// The names given by `hide_names`, which are "Aさん" to "Dさん".
+    #[must_use]
+    pub fn anonymous_names() -> [String; 4] {
+        ['A', 'B', 'C', 'D'].map(|alias| format!("{alias}さん"))
     }
 
     #[inline]
@@ -146,13 +145,13 @@ impl RawLog {
 
     #[inline]
     #[must_use]
-    pub fn is_empty(&self) -> bool {
+    pub const fn is_empty(&self) -> bool {
         self.logs.is_empty()
     }
 
     #[inline]
     #[must_use]
-    pub fn len(&self) -> usize {
+    pub const fn len(&self) -> usize {
         self.logs.len()
     }
 }
@@ -1,7 +1,7 @@
 use super::json_scheme::{ActionItem, KyokuMeta, RawLog, ResultItem};
 use crate::{KyokuFilter, Tile};
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::{self as json, Value};
 use thiserror::Error;
 
@@ -27,7 +27,7 @@ pub struct Log {
     pub kyokus: Vec<Kyoku>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum GameLength {
     Hanchan = 0,
     Tonpuu = 4,
@@ -0,0 +1,44 @@
+mod testdata;
+
+use convlog::*;
+use testdata::{TESTDATA, TestCase};
+
+#[test]
+fn test_replay_and_seek() {
+    TESTDATA.iter().for_each(|TestCase { desc, data }| {
+        let tenhou_log = tenhou::Log::from_json_str(data)
+            .unwrap_or_else(|_| panic!("failed to parse tenhou log (case: {desc})"));
+        let events = tenhou_to_mjai(&tenhou_log)
+            .unwrap_or_else(|_| panic!("failed to transform tenhou log (case: {desc})"));
+
+        let mut replay = Replay::new(&events);
+        let mut boards = vec![replay.snapshot()];
+        while let Some(board) = replay.step() {
+            let board = board.unwrap_or_else(|e| panic!("failed to replay (case: {desc}): {e}"));
+            if board.bakaze.is_none() {
+                // not started yet
+                boards.push(replay.snapshot());
+                continue;
+            }
+            for (seat, s) in board.seats.iter().enumerate() {
+                let tiles = s.tehai.len() + usize::from(s.tsumo.is_some());
+                let called = s.fuuros.len() * 3;
+                assert!(
+                    matches!(tiles + called, 13 | 14),
+                    "wrong hand size of seat {seat} at event #{} (case: {desc})",
+                    replay.position() - 1,
+                );
+                assert!(s.is_hand_known());
+            }
+            boards.push(replay.snapshot());
+        }
+        assert_eq!(replay.position(), events.len());
+
+        // seeking back and forth gives the same boards as a straight replay
+        for pos in (0..=events.len()).rev().step_by(7).chain([3, events.len()]) {
+            let board = replay.seek(pos).unwrap();
+            assert_eq!(*board, boards[pos], "seek to {pos} (case: {desc})");
+        }
+        replay.seek(events.len() + 1).unwrap_err();
+    });
+}
@@ -0,0 +1,53 @@
+mod testdata;
+
+use convlog::*;
+use testdata::{TESTDATA, TestCase};
+
+#[test]
+fn test_shanten_of_winning_hands() {
+    TESTDATA.iter().for_each(|TestCase { desc, data }| {
+        if *desc == "confusing_nakis_2" {
+            // made up for the calls, it does not end with a real winning hand
+            return;
+        }
+        let tenhou_log = tenhou::Log::from_json_str(data)
+            .unwrap_or_else(|_| panic!("failed to parse tenhou log (case: {desc})"));
+        let events = tenhou_to_mjai(&tenhou_log)
+            .unwrap_or_else(|_| panic!("failed to transform tenhou log (case: {desc})"));
+
+        let mut replay = Replay::new(&events);
+        let mut last_pai = None;
+        for (pos, event) in events.iter().enumerate() {
+            match *event {
+                Event::Dahai { pai, .. } | Event::Kakan { pai, .. } => last_pai = Some(pai),
+                Event::Hora { actor, target, .. } => {
+                    let board = replay.seek(pos).unwrap();
+                    let seat = &board.seats[actor as usize];
+                    let mut tehai = seat.counts();
+                    let agari_pai = if actor == target {
+                        seat.tsumo.unwrap()
+                    } else {
+                        let pai = last_pai.unwrap();
+                        tehai[pai.deaka().as_usize()] += 1;
+                        pai
+                    };
+                    let len_div3 = 4 - seat.fuuros.len() as u8;
+
+                    assert_eq!(
+                        shanten::calc_all(&tehai, len_div3),
+                        -1,
+                        "hora at event #{pos} (case: {desc})",
+                    );
+
+                    tehai[agari_pai.deaka().as_usize()] -= 1;
+                    assert_eq!(
+                        shanten::calc_all(&tehai, len_div3),
+                        0,
+                        "tenpai before hora at event #{pos} (case: {desc})",
+                    );
+                }
+                _ => (),
+            }
+        }
+    });
+}
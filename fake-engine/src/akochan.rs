@@ -0,0 +1,76 @@
+use crate::player::{Decision, Player};
+use crate::{Config, Input, value};
+use convlog::Event;
+use std::io::prelude::*;
+
+use anyhow::{Context, Result};
+use serde_json::{self as json, json};
+
+/// Above this shanten, the values are left out for the own turn, like akochan
+/// does in the rule based phase.
+const RULE_BASE_SHANTEN: i8 = 4;
+
+/// Speaks the `pipe_detailed` protocol of akochan: a JSON array of candidate
+/// actions, best first, after every discard or kakan of an opponent and every
+/// own tsumo. Nothing is written for other events.
+pub fn run(
+    config: &Config,
+    mut input: Input<'_>,
+    mut out: impl Write,
+    player_id: u8,
+) -> Result<()> {
+    let mut player = Player::new(player_id);
+
+    while let Some(line) = input.next_line()? {
+        let event: Event = json::from_str(&line).context("failed to parse event")?;
+        let decision = player.update(&event)?;
+        if !matches!(
+            decision,
+            Decision::OwnTurn | Decision::Call { .. } | Decision::Chankan { .. }
+        ) {
+            continue;
+        }
+
+        let mut candidates = player.candidates(decision);
+        if candidates.is_empty() {
+            candidates.push(vec![Event::None]);
+        }
+        let rule_based = decision == Decision::OwnTurn && player.shanten() >= RULE_BASE_SHANTEN;
+        let step = input.count() as u64;
+
+        let mut actions: Vec<_> = candidates
+            .into_iter()
+            .enumerate()
+            .map(|(i, moves)| {
+                let v = |k: u64| value(config.seed, &[step, i as u64, k]);
+                (v(0), moves, v(1), v(2))
+            })
+            .collect();
+        actions.sort_by(|(l, ..), (r, ..)| r.total_cmp(l));
+
+        let output: Vec<_> = actions
+            .into_iter()
+            .map(|(pt, moves, prob, pt_after)| {
+                let review = if rule_based {
+                    json!({
+                        "total_houjuu_hai_prob_now": null,
+                        "total_houjuu_hai_value_now": null,
+                        "pt_exp_after": null,
+                        "pt_exp_total": null,
+                    })
+                } else {
+                    json!({
+                        "total_houjuu_hai_prob_now": prob * 0.3,
+                        "total_houjuu_hai_value_now": prob * 8000.,
+                        "pt_exp_after": pt_after,
+                        "pt_exp_total": pt,
+                    })
+                };
+                json!({ "moves": moves, "review": review })
+            })
+            .collect();
+        writeln!(out, "{}", json::to_string(&output)?)?;
+        out.flush()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,183 @@
+//! A fake mjai engine for end-to-end tests of mjai-reviewer.
+//!
+//! It speaks either the review mode protocol of Mortal or the `pipe_detailed`
+//! protocol of akochan, depending on how it is invoked:
+//!
+//! ```sh
+//! MORTAL_CFG=config.toml fake-engine <player_id>
+//! fake-engine pipe_detailed <tactics.json> <player_id>
+//! ```
+//!
+//! It follows the game and only offers legal actions, but the values given to
+//! them are pseudo random numbers derived from a seed, so its answers are
+//! always the same for the same input. It can be configured with a `[fake]`
+//! table in Mortal's config.toml or a `"fake"` object in akochan's
+//! tactics.json, see [`Config`].
+
+#![deny(
+    rust_2018_idioms,
+    let_underscore_drop,
+    clippy::semicolon_if_nothing_returned,
+    clippy::uninlined_format_args,
+    clippy::use_self
+)]
+
+mod akochan;
+mod mortal;
+mod player;
+
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{env, fs, io, process, thread};
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use serde_json as json;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Seed of the values given to actions.
+    pub seed: u64,
+    /// Reported as `model_tag` in Mortal mode.
+    pub model_tag: Option<String>,
+    /// Exit with code 1 right after reading this many lines.
+    pub crash_after: Option<usize>,
+    /// Only crash if this file does not exist yet, creating it, so that the
+    /// engine crashes once and works after a restart.
+    pub crash_once: Option<PathBuf>,
+    /// Stop responding after reading this many lines.
+    pub hang_after: Option<usize>,
+    /// Write a line to stderr for every line read.
+    pub verbose: bool,
+    /// Report every n-th response as not greedy in Mortal mode.
+    pub sample_every: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    fake: Config,
+}
+
+impl Config {
+    fn load_toml(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let file: ConfigFile = toml::from_str(&content)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+        Ok(file.fake)
+    }
+
+    fn load_json(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let file: ConfigFile = json::from_str(&content)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+        Ok(file.fake)
+    }
+}
+
+/// Reads lines from stdin, applying the faults configured.
+pub struct Input<'a> {
+    config: &'a Config,
+    lines: io::Lines<io::StdinLock<'static>>,
+    count: usize,
+}
+
+impl<'a> Input<'a> {
+    fn new(config: &'a Config) -> Self {
+        Self {
+            config,
+            lines: io::stdin().lock().lines(),
+            count: 0,
+        }
+    }
+
+    pub fn next_line(&mut self) -> Result<Option<String>> {
+        let Some(line) = self.lines.next().transpose()? else {
+            return Ok(None);
+        };
+        self.count += 1;
+        if self.config.verbose {
+            eprintln!("fake-engine: line #{}", self.count);
+        }
+        if self.config.crash_after == Some(self.count)
+            && self
+                .config
+                .crash_once
+                .as_ref()
+                .is_none_or(|marker| fs::File::create_new(marker).is_ok())
+        {
+            eprintln!("fake-engine: crashing as configured");
+            process::exit(1);
+        }
+        if self.config.hang_after == Some(self.count) {
+            eprintln!("fake-engine: hanging as configured");
+            loop {
+                thread::sleep(Duration::from_secs(3600));
+            }
+        }
+        Ok(Some(line))
+    }
+
+    /// Number of lines read so far.
+    pub const fn count(&self) -> usize {
+        self.count
+    }
+}
+
+/// Returns a value within [0, 1) determined by `seed` and `keys`.
+pub fn value(seed: u64, keys: &[u64]) -> f64 {
+    let mut x = seed;
+    for &k in keys {
+        x = splitmix64(x ^ k);
+    }
+    (splitmix64(x) >> 11) as f64 / (1_u64 << 53) as f64
+}
+
+const fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn parse_player_id(s: &str) -> Result<u8> {
+    let id: u8 = s.parse().context("invalid player ID")?;
+    if id >= 4 {
+        bail!("player ID {id} is not within 0-3");
+    }
+    Ok(id)
+}
+
+fn run() -> Result<()> {
+    let args: Vec<_> = env::args().skip(1).collect();
+    let stdout = io::stdout().lock();
+    match args.as_slice() {
+        [mode, tactics, player_id] if mode == "pipe_detailed" => {
+            let config = Config::load_json(Path::new(tactics))?;
+            let player_id = parse_player_id(player_id)?;
+            akochan::run(&config, Input::new(&config), stdout, player_id)
+        }
+        [player_id] => {
+            let config = match env::var_os("MORTAL_CFG") {
+                Some(path) => Config::load_toml(Path::new(&path))?,
+                None => Config::default(),
+            };
+            let player_id = parse_player_id(player_id)?;
+            mortal::run(&config, Input::new(&config), stdout, player_id)
+        }
+        _ => bail!(
+            "usage: fake-engine <player_id> | fake-engine pipe_detailed <tactics.json> <player_id>"
+        ),
+    }
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("fake-engine: {err:?}");
+        process::exit(1);
+    }
+}
@@ -0,0 +1,157 @@
+use crate::player::{Decision, Player};
+use crate::{Config, Input, value};
+use convlog::Event;
+use std::io::prelude::*;
+
+use anyhow::{Context, Result};
+use serde_json::{self as json, Value, json};
+
+/// Speaks the review mode protocol of Mortal: one line of response for every
+/// event, with `meta` when there is a choice to make, followed by a line of
+/// extra data once stdin is closed.
+pub fn run(
+    config: &Config,
+    mut input: Input<'_>,
+    mut out: impl Write,
+    player_id: u8,
+) -> Result<()> {
+    let mut player = Player::new(player_id);
+    let mut kyokus = 0;
+
+    while let Some(line) = input.next_line()? {
+        let event: Event = json::from_str(&line).context("failed to parse event")?;
+        let decision = player.update(&event)?;
+        if event == Event::EndKyoku {
+            kyokus += 1;
+        }
+        let response = respond(config, &player, decision, input.count() as u64);
+        writeln!(out, "{response}")?;
+        out.flush()?;
+    }
+
+    let phi_matrix: Vec<_> = (0..kyokus)
+        .map(|k| {
+            let mut matrix = [[0.; 4]; 4];
+            for (i, row) in matrix.iter_mut().enumerate() {
+                for (j, v) in row.iter_mut().enumerate() {
+                    *v = value(config.seed, &[u64::MAX, k, i as u64, j as u64]) + 0.01;
+                }
+                let sum: f64 = row.iter().sum();
+                row.iter_mut().for_each(|v| *v /= sum);
+            }
+            matrix
+        })
+        .collect();
+    let extra = json!({
+        "model_tag": config.model_tag.as_deref().unwrap_or("fake"),
+        "phi_matrix": phi_matrix,
+    });
+    writeln!(out, "{extra}")?;
+    out.flush()?;
+    Ok(())
+}
+
+fn respond(config: &Config, player: &Player, decision: Decision, step: u64) -> Value {
+    let candidates = player.candidates(decision);
+    if candidates.is_empty() {
+        return json!({"type": "none"});
+    }
+
+    let mut mask_bits = 0_u64;
+    let mut kan_mask_bits = 0_u64;
+    let mut actions: [Option<&Event>; 46] = [None; 46];
+    let mut kans: [Option<&Event>; 34] = [None; 34];
+    for moves in &candidates {
+        let action = &moves[0];
+        let label = to_label(action);
+        mask_bits |= 1 << label;
+        actions[label].get_or_insert(action);
+        // kan select is only for own turn
+        if decision == Decision::OwnTurn
+            && let Some(kan_label) = to_kan_label(action)
+        {
+            kan_mask_bits |= 1 << kan_label;
+            kans[kan_label].get_or_insert(action);
+        }
+    }
+
+    let q_of = |label: usize| (value(config.seed, &[step, label as u64]) * 2. - 1.) as f32;
+    let labels: Vec<_> = (0..46).filter(|&l| actions[l].is_some()).collect();
+    let q_values: Vec<_> = labels.iter().map(|&l| q_of(l)).collect();
+    let kan_labels: Vec<_> = (0..34).filter(|&l| kans[l].is_some()).collect();
+    let kan_q_values: Vec<_> = kan_labels.iter().map(|&l| q_of(100 + l)).collect();
+
+    let best = argmax(&labels, &q_values);
+    let expected = if kan_labels.is_empty() || best != 42 {
+        actions[best]
+    } else {
+        kans[argmax(&kan_labels, &kan_q_values)]
+    };
+
+    let is_greedy = config.sample_every.is_none_or(|n| !step.is_multiple_of(n));
+    let mut meta = json!({
+        "q_values": q_values,
+        "mask_bits": mask_bits,
+        "is_greedy": is_greedy,
+        "batch_size": 1,
+        "eval_time_ns": 1_000_000 + step * 1000,
+        "shanten": player.shanten(),
+        "at_furiten": player.at_furiten(),
+    });
+    if !kan_labels.is_empty() {
+        meta["kan_select"] = json!({
+            "q_values": kan_q_values,
+            "mask_bits": kan_mask_bits,
+            "is_greedy": true,
+            "batch_size": 1,
+            "eval_time_ns": 1_000_000 + step * 1000,
+        });
+    }
+
+    // unwrap is safe because there is at least one candidate
+    let mut response = json::to_value(expected.unwrap()).unwrap();
+    response["meta"] = meta;
+    response
+}
+
+fn argmax(labels: &[usize], q_values: &[f32]) -> usize {
+    labels
+        .iter()
+        .zip(q_values)
+        .max_by(|(_, l), (_, r)| l.total_cmp(r))
+        .map(|(&label, _)| label)
+        .unwrap_or_default()
+}
+
+/// Same as `to_label` in mjai-reviewer:/src/review/mortal.rs.
+fn to_label(ev: &Event) -> usize {
+    match ev {
+        Event::Dahai { pai, .. } => pai.as_usize(),
+        Event::Reach { .. } => 37,
+        Event::Chi { pai, consumed, .. } => {
+            let a = consumed[0].deaka().as_usize();
+            let b = consumed[1].deaka().as_usize();
+            let x = pai.deaka().as_usize();
+            if x < a.min(b) {
+                38
+            } else if x < a.max(b) {
+                39
+            } else {
+                40
+            }
+        }
+        Event::Pon { .. } => 41,
+        Event::Daiminkan { .. } | Event::Ankan { .. } | Event::Kakan { .. } => 42,
+        Event::Hora { .. } => 43,
+        Event::Ryukyoku { .. } => 44,
+        _ => 45,
+    }
+}
+
+const fn to_kan_label(ev: &Event) -> Option<usize> {
+    match ev {
+        Event::Ankan { consumed, .. } => Some(consumed[0].deaka().as_usize()),
+        Event::Kakan { pai, .. } => Some(pai.deaka().as_usize()),
+        _ => None,
+    }
+}
@@ -0,0 +1,483 @@
+use convlog::shanten;
+use convlog::{Event, Tile};
+
+use anyhow::{Context, Result};
+
+/// What the player is asked to do right after an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    None,
+    /// After a tsumo, including rinshan.
+    OwnTurn,
+    /// After an own chi or pon.
+    AfterCall,
+    /// After an own reach, before the discard.
+    AfterReach,
+    /// After a discard of an opponent.
+    Call {
+        target: u8,
+        pai: Tile,
+    },
+    /// After a kakan of an opponent.
+    Chankan {
+        target: u8,
+        pai: Tile,
+    },
+}
+
+/// Follows the game from the perspective of one player, just enough to tell
+/// the legal actions.
+///
+/// Some rules are relaxed, for example kuikae is not prohibited and temporary
+/// furiten is ignored, so the actions offered are a superset of the legal
+/// ones.
+pub struct Player {
+    pub id: u8,
+    tehai: Vec<Tile>,
+    /// Exact tiles of own pons, for kakan.
+    pons: Vec<[Tile; 3]>,
+    melds: u8,
+    is_menzen: bool,
+    discards: Vec<Tile>,
+    in_riichi: bool,
+    score: i32,
+    tiles_left: u8,
+    /// Total kans on the table.
+    kans: u8,
+    /// No call has been made by anyone in this kyoku.
+    uninterrupted: bool,
+    last_drawn: Option<Tile>,
+}
+
+impl Player {
+    pub const fn new(id: u8) -> Self {
+        Self {
+            id,
+            tehai: vec![],
+            pons: vec![],
+            melds: 0,
+            is_menzen: true,
+            discards: vec![],
+            in_riichi: false,
+            score: 25000,
+            tiles_left: 70,
+            kans: 0,
+            uninterrupted: true,
+            last_drawn: None,
+        }
+    }
+
+    pub fn update(&mut self, event: &Event) -> Result<Decision> {
+        let id = self.id;
+        let decision = match *event {
+            Event::StartKyoku { scores, tehais, .. } => {
+                *self = Self::new(id);
+                self.tehai = tehais[id as usize].to_vec();
+                self.score = scores[id as usize];
+                Decision::None
+            }
+
+            Event::Tsumo { actor, pai } => {
+                self.tiles_left = self.tiles_left.saturating_sub(1);
+                if actor != id {
+                    return Ok(Decision::None);
+                }
+                self.tehai.push(pai);
+                self.last_drawn = Some(pai);
+                Decision::OwnTurn
+            }
+
+            Event::Dahai { actor, pai, .. } => {
+                if actor != id {
+                    return Ok(Decision::Call { target: actor, pai });
+                }
+                self.remove(&[pai])?;
+                self.discards.push(pai);
+                self.last_drawn = None;
+                Decision::None
+            }
+
+            Event::Chi {
+                actor, consumed, ..
+            } => {
+                self.uninterrupted = false;
+                if actor != id {
+                    return Ok(Decision::None);
+                }
+                self.remove(&consumed)?;
+                self.melds += 1;
+                self.is_menzen = false;
+                self.last_drawn = None;
+                Decision::AfterCall
+            }
+
+            Event::Pon {
+                actor,
+                pai,
+                consumed,
+                ..
+            } => {
+                self.uninterrupted = false;
+                if actor != id {
+                    return Ok(Decision::None);
+                }
+                self.remove(&consumed)?;
+                self.pons.push([pai, consumed[0], consumed[1]]);
+                self.melds += 1;
+                self.is_menzen = false;
+                self.last_drawn = None;
+                Decision::AfterCall
+            }
+
+            Event::Daiminkan {
+                actor, consumed, ..
+            } => {
+                self.uninterrupted = false;
+                self.kans += 1;
+                if actor == id {
+                    self.remove(&consumed)?;
+                    self.melds += 1;
+                    self.is_menzen = false;
+                }
+                Decision::None
+            }
+
+            Event::Ankan { actor, consumed } => {
+                self.uninterrupted = false;
+                self.kans += 1;
+                if actor == id {
+                    self.remove(&consumed)?;
+                    self.melds += 1;
+                }
+                Decision::None
+            }
+
+            Event::Kakan { actor, pai, .. } => {
+                self.uninterrupted = false;
+                self.kans += 1;
+                if actor != id {
+                    return Ok(Decision::Chankan { target: actor, pai });
+                }
+                self.remove(&[pai])?;
+                self.pons.retain(|p| p[0].deaka() != pai.deaka());
+                Decision::None
+            }
+
+            Event::Reach { actor } if actor == id => Decision::AfterReach,
+
+            Event::ReachAccepted { actor } if actor == id => {
+                self.in_riichi = true;
+                self.score -= 1000;
+                Decision::None
+            }
+
+            _ => Decision::None,
+        };
+        Ok(decision)
+    }
+
+    /// Lists the legal actions for `decision`, each as the events it consists
+    /// of, the same as the `moves` of akochan.
+    pub fn candidates(&self, decision: Decision) -> Vec<Vec<Event>> {
+        let actor = self.id;
+        let mut ret = vec![];
+        match decision {
+            Decision::None => (),
+
+            Decision::OwnTurn => {
+                if self.shanten() == -1 {
+                    ret.push(vec![Event::Hora {
+                        actor,
+                        target: actor,
+                        deltas: None,
+                        ura_markers: None,
+                    }]);
+                }
+                if self.can_kyushukyuhai() {
+                    ret.push(vec![Event::Ryukyoku { deltas: None }]);
+                }
+                if self.tiles_left > 0 && self.kans < 4 {
+                    ret.extend(self.kans().into_iter().map(|k| vec![k]));
+                }
+                if self.in_riichi {
+                    if let Some(pai) = self.last_drawn {
+                        ret.push(vec![Event::Dahai {
+                            actor,
+                            pai,
+                            tsumogiri: true,
+                        }]);
+                    }
+                    return ret;
+                }
+                if self.is_menzen && self.tiles_left >= 4 && self.score >= 1000 {
+                    for dahai in self.discards_keeping_tenpai() {
+                        ret.push(vec![Event::Reach { actor }, dahai]);
+                    }
+                }
+                ret.extend(self.discards_from(&self.tehai).into_iter().map(|d| vec![d]));
+            }
+
+            Decision::AfterCall => {
+                ret.extend(self.discards_from(&self.tehai).into_iter().map(|d| vec![d]));
+            }
+
+            Decision::AfterReach => {
+                ret.extend(self.discards_keeping_tenpai().into_iter().map(|d| vec![d]));
+            }
+
+            Decision::Call { target, pai } => {
+                if self.can_ron(pai) {
+                    ret.push(vec![Event::Hora {
+                        actor,
+                        target,
+                        deltas: None,
+                        ura_markers: None,
+                    }]);
+                }
+                if !self.in_riichi && self.tiles_left > 0 {
+                    self.calls(target, pai, &mut ret);
+                }
+                if !ret.is_empty() {
+                    ret.push(vec![Event::None]);
+                }
+            }
+
+            Decision::Chankan { target, pai } => {
+                if self.can_ron(pai) {
+                    ret.push(vec![Event::Hora {
+                        actor,
+                        target,
+                        deltas: None,
+                        ura_markers: None,
+                    }]);
+                    ret.push(vec![Event::None]);
+                }
+            }
+        }
+        ret
+    }
+
+    /// Shanten of the hand as it is.
+    pub fn shanten(&self) -> i8 {
+        shanten::calc_all(&counts(&self.tehai), 4 - self.melds)
+    }
+
+    /// Whether any of the waits of a tenpai hand has been discarded by the
+    /// player.
+    pub fn at_furiten(&self) -> bool {
+        if self.tehai.len() % 3 != 1 {
+            return false;
+        }
+        let mut tehai = counts(&self.tehai);
+        self.discards.iter().any(|d| {
+            let id = d.deaka().as_usize();
+            tehai[id] += 1;
+            let agari = shanten::calc_all(&tehai, 4 - self.melds) == -1;
+            tehai[id] -= 1;
+            agari
+        })
+    }
+
+    fn can_ron(&self, pai: Tile) -> bool {
+        let mut tehai = counts(&self.tehai);
+        tehai[pai.deaka().as_usize()] += 1;
+        shanten::calc_all(&tehai, 4 - self.melds) == -1 && !self.at_furiten()
+    }
+
+    fn can_kyushukyuhai(&self) -> bool {
+        if !self.uninterrupted || !self.discards.is_empty() {
+            return false;
+        }
+        let tehai = counts(&self.tehai);
+        let kinds = (0..34)
+            .filter(|&id| tehai[id] > 0 && must_tile(id).is_yaokyuu())
+            .count();
+        kinds >= 9
+    }
+
+    fn kans(&self) -> Vec<Event> {
+        let actor = self.id;
+        let tehai = counts(&self.tehai);
+        let mut ret = vec![];
+        for (id, &count) in tehai.iter().enumerate() {
+            if count < 4 {
+                continue;
+            }
+            if self.in_riichi && self.last_drawn.map(Tile::deaka) != Some(must_tile(id)) {
+                continue;
+            }
+            let mut consumed = [Tile::default(); 4];
+            let tiles = self.tehai.iter().filter(|t| t.deaka().as_usize() == id);
+            for (c, &t) in consumed.iter_mut().zip(tiles) {
+                *c = t;
+            }
+            consumed.sort();
+            ret.push(Event::Ankan { actor, consumed });
+        }
+        if self.in_riichi {
+            return ret;
+        }
+        for pon in &self.pons {
+            if let Some(&pai) = self.tehai.iter().find(|t| t.deaka() == pon[0].deaka()) {
+                ret.push(Event::Kakan {
+                    actor,
+                    pai,
+                    consumed: *pon,
+                });
+            }
+        }
+        ret
+    }
+
+    fn calls(&self, target: u8, pai: Tile, ret: &mut Vec<Vec<Event>>) {
+        let actor = self.id;
+
+        // pon and daiminkan
+        let same: Vec<_> = self
+            .tehai
+            .iter()
+            .copied()
+            .filter(|t| t.deaka() == pai.deaka())
+            .collect();
+        if same.len() >= 2 {
+            for consumed in distinct_pairs(&same) {
+                let Some(rest) = remove_from(&self.tehai, &consumed) else {
+                    continue;
+                };
+                let pon = Event::Pon {
+                    actor,
+                    target,
+                    pai,
+                    consumed,
+                };
+                for dahai in self.discards_from(&rest) {
+                    ret.push(vec![pon.clone(), dahai]);
+                }
+            }
+        }
+        if same.len() >= 3 && self.kans < 4 {
+            ret.push(vec![Event::Daiminkan {
+                actor,
+                target,
+                pai,
+                consumed: [same[0], same[1], same[2]],
+            }]);
+        }
+
+        // chi, only from kamicha
+        if (target + 1) % 4 != actor || pai.is_jihai() {
+            return;
+        }
+        let id = pai.deaka().as_usize();
+        let num = id % 9;
+        let patterns = [
+            (num <= 6).then(|| [id + 1, id + 2]),
+            (1..=7).contains(&num).then(|| [id - 1, id + 1]),
+            (num >= 2).then(|| [id - 2, id - 1]),
+        ];
+        for [a, b] in patterns.into_iter().flatten() {
+            let of_kind = |k| -> Vec<Tile> {
+                let mut tiles: Vec<_> = self
+                    .tehai
+                    .iter()
+                    .copied()
+                    .filter(|t| t.deaka().as_usize() == k)
+                    .collect();
+                tiles.sort();
+                tiles.dedup();
+                tiles
+            };
+            for &x in &of_kind(a) {
+                for &y in &of_kind(b) {
+                    let consumed = [x, y];
+                    let Some(rest) = remove_from(&self.tehai, &consumed) else {
+                        continue;
+                    };
+                    let chi = Event::Chi {
+                        actor,
+                        target,
+                        pai,
+                        consumed,
+                    };
+                    for dahai in self.discards_from(&rest) {
+                        ret.push(vec![chi.clone(), dahai]);
+                    }
+                }
+            }
+        }
+    }
+
+    /// `last_drawn` must be `None` unless `tehai` is the current hand.
+    fn discards_from(&self, tehai: &[Tile]) -> Vec<Event> {
+        let mut tiles = tehai.to_vec();
+        tiles.sort();
+        tiles.dedup();
+        tiles
+            .into_iter()
+            .map(|pai| Event::Dahai {
+                actor: self.id,
+                pai,
+                tsumogiri: self.last_drawn == Some(pai),
+            })
+            .collect()
+    }
+
+    fn discards_keeping_tenpai(&self) -> Vec<Event> {
+        let mut tehai = counts(&self.tehai);
+        self.discards_from(&self.tehai)
+            .into_iter()
+            .filter(|d| {
+                let Event::Dahai { pai, .. } = *d else {
+                    return false;
+                };
+                let id = pai.deaka().as_usize();
+                tehai[id] -= 1;
+                let tenpai = shanten::calc_all(&tehai, 4 - self.melds) == 0;
+                tehai[id] += 1;
+                tenpai
+            })
+            .collect()
+    }
+
+    fn remove(&mut self, tiles: &[Tile]) -> Result<()> {
+        self.tehai = remove_from(&self.tehai, tiles)
+            .with_context(|| format!("{tiles:?} not found in {:?}", self.tehai))?;
+        Ok(())
+    }
+}
+
+fn counts(tiles: &[Tile]) -> [u8; 34] {
+    let mut ret = [0; 34];
+    for t in tiles {
+        ret[t.deaka().as_usize()] += 1;
+    }
+    ret
+}
+
+fn must_tile(id: usize) -> Tile {
+    Tile::try_from(id).unwrap()
+}
+
+fn remove_from(tehai: &[Tile], tiles: &[Tile]) -> Option<Vec<Tile>> {
+    let mut ret = tehai.to_vec();
+    for t in tiles {
+        let idx = ret.iter().position(|x| x == t)?;
+        ret.remove(idx);
+    }
+    Some(ret)
+}
+
+/// Distinct pairs of tiles from `tiles`, which only differ in aka.
+fn distinct_pairs(tiles: &[Tile]) -> Vec<[Tile; 2]> {
+    let mut ret = vec![];
+    for (i, &a) in tiles.iter().enumerate() {
+        for &b in &tiles[i + 1..] {
+            let mut pair = [a, b];
+            pair.sort();
+            if !ret.contains(&pair) {
+                ret.push(pair);
+            }
+        }
+    }
+    ret
+}
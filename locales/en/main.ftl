@@ -10,35 +10,106 @@ action-ron = Ron
 action-ryuukyoku = Ryuukyoku
 action-tsumo = Tsumo
 
+board-dealer = (dealer)
+board-header = table
+board-info = {$bakaze}{$kyoku}-{$honba}, kyotaku {$kyotaku}, {$tiles-left} tiles left
+board-riichi = riichi
+
+categories-header = Decision categories
+category-agari = ron/tsumo
+category-call = chi/pon
+category-decisions-header = decisions
+category-defense = discard facing riichi
+category-efficiency = discard efficiency
+category-header = category
+category-kan = kan
+category-kyushukyuhai = kyushukyuhai
+category-loss-header =
+    {$engine ->
+        [Mortal] Q-value
+        *[other] pt EV
+    } loss (mean)
+category-match-rate-header = match rate (%)
+category-matches-header = matches
+category-riichi = riichi vs dama
+category-tolerated-header = tolerated
+
+defense-deal-in-prob-header = deal-in (%)
+defense-deal-in-value-header = deal-in value
+defense-discard-header = discard
+defense-header = Defense
+defense-risky-deal-in = dealt in after a high risk discard
+defense-safest-header = safest
+defense-turn-header = turn
+
 donate-header = Donate
 
 end-status-ron = {action-ron} by{"\u00a0"}{$seat}{$delta}
 end-status-ryuukyoku = {action-ryuukyoku}
 end-status-tsumo = {action-tsumo} by{"\u00a0"}{$seat}{$delta}
 
+engine-log-dropped =
+    ({$count} earlier {$count ->
+        [one] line
+        *[other] lines
+    } dropped)
+engine-log-empty = The engine wrote nothing to stderr, or its output was cached.
+engine-log-header = engine log
+
 final-ranking-probs-at-the-start-of-kyoku = Final ranking probs at the start of {$kyoku}
 
 game-summary-header = Game Summary
 
 help-header = Help
 
+hindsight-deal-in = the discard would deal in to
+hindsight-header = hindsight
+hindsight-next-draws = next draws:
+hindsight-note = From the hidden hands and the rest of the log, which the engine never sees.
+hindsight-shanten = {$shanten}-shanten
+hindsight-tenpai = tenpai
+hindsight-waiting = tenpai, waiting for
+
 kyoku =
     {$bakaze} {$kyoku-in-bakaze}{$honba ->
         [0] {""}
         *[other] -{$honba}
     }
 
+metadata-deal-in-risk-header = deal-in risk taken / necessary (mean)
+metadata-deal-in-value-header = deal-in value taken / necessary (total)
+metadata-deal-ins-header = deal-ins (after high risk discards)
 metadata-engine-header = AI engine
 metadata-game-length-header = game length
 metadata-game-length-value = {$length}
 metadata-generated-at-header = generated at
 metadata-header = Metadata
+metadata-high-risk-header = high risk discards
+metadata-inference-time-header = inference time (total / p50 / p90 / p99 / max)
 metadata-loading-time-header = loading time
 metadata-log-id-header = log id
+metadata-loss-header =
+    {$engine ->
+        [Mortal] Q-value
+        *[other] pt EV
+    } loss (total / mean)
 metadata-match-rate-header = matches/total
+metadata-max-batch-size-header = max batch size
+metadata-mean-prob-header = mean probability of actual actions
+metadata-mistakes-header = weighted mistakes
 metadata-mjai-reviewer-version-header = mjai-reviewer version
+metadata-model-tag-header = model tag
+metadata-non-greedy-header = non-greedy decisions
 metadata-player-id-header = player id
+metadata-rating-header = rating
 metadata-review-time-header = review time
+metadata-temperature-header = softmax temperature (τ)
+metadata-tolerance-header = tolerance
+metadata-tolerance-prob-ratio = probability ratio ≥ {$ratio}
+metadata-tolerance-q-gap = Q-value gap ≤ {$gap}
+metadata-tolerated-header = tolerated/total
+metadata-top-k-header = top-k agreement
+metadata-top-k-value = top-{$k}
 
 panel-expand = Expand:
 panel-expand-all = All
@@ -60,9 +131,23 @@ place-percentage =
 player = Player
 
 replay-viewer = Replay viewer
+show-in-replay-viewer = Show in replay viewer
+
+safety-against = against {$seat}:
+safety-genbutsu = genbutsu
+safety-header = safe tiles
+safety-honor = honor, {$visible} visible
+safety-no-chance = no-chance
+safety-one-chance = one-chance
+safety-suji = suji
+safety-unsafe = unsafe
 
 score-header = Score
 
+severity-blunder = blunder
+severity-minor = minor
+severity-mistake = mistake
+
 tehai-cuts = {$player} cuts
 tehai-draw = Draw
 tehai-kans = {$player} kans
@@ -75,9 +160,18 @@ tenhou-net-6-paste-instruction-after-link = {" "}- EDIT AS TEXT.
 
 title = Replay Examination
 
+top-mistakes-header = Top mistakes
+top-mistakes-loss-header =
+    {$engine ->
+        [Mortal] Q-value
+        *[other] pt EV
+    } loss
+top-mistakes-severity-header = severity
+
 turn = Turn {$junme} (×{$tiles-left})
 
 turn-info-furiten = (furiten)
+turn-info-loss = loss
 turn-info-shanten = {$shanten} shanten
 turn-info-tenpai = tenpai
 
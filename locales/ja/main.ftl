@@ -10,18 +10,62 @@ action-ron = ロン
 action-ryuukyoku = 流局
 action-tsumo = ツモ
 
+board-dealer = (親)
+board-header = 卓
+board-info = {$bakaze}{$kyoku}-{$honba}、供託 {$kyotaku}、残り{$tiles-left}枚
+board-riichi = 立直
+
+categories-header = 判断の種類
+category-agari = ロン/ツモ
+category-call = チー/ポン
+category-decisions-header = 判断数
+category-defense = 立直に対する打牌
+category-efficiency = 牌効率
+category-header = 種類
+category-kan = カン
+category-kyushukyuhai = 九種九牌
+category-loss-header =
+    {$engine ->
+        [Mortal] Q値
+        *[other] pt期待値
+    }損失 (平均)
+category-match-rate-header = 一致率 (%)
+category-matches-header = 一致数
+category-riichi = 立直かダマか
+category-tolerated-header = 許容数
+
+defense-deal-in-prob-header = 放銃率 (%)
+defense-deal-in-value-header = 放銃打点
+defense-discard-header = 打牌
+defense-header = 守備
+defense-risky-deal-in = 危険牌を切った後の放銃
+defense-safest-header = 最も安全な牌
+defense-turn-header = 巡目
+
 donate-header = 寄付
 
 end-status-ron = {$seat}{"\u00a0"}{action-ron} {$delta}
 end-status-ryuukyoku = {action-ryuukyoku}
 end-status-tsumo = {$seat}{"\u00a0"}{action-tsumo} {$delta}
 
+engine-log-dropped = (以前の{$count}行は省略)
+engine-log-empty = エンジンは標準エラー出力に何も書き込まなかったか、出力がキャッシュされていました。
+engine-log-header = エンジンログ
+
 final-ranking-probs-at-the-start-of-kyoku = {$kyoku}開始時点の最終順位確率
 
 game-summary-header = 目次
 
 help-header = ヘルプ
 
+hindsight-deal-in = この打牌の放銃先:
+hindsight-header = 結果論
+hindsight-next-draws = 次のツモ:
+hindsight-note = 伏せられた手牌と以降の牌譜によるもので、AIには見えない情報です。
+hindsight-shanten = {$shanten}向聴
+hindsight-tenpai = 聴牌
+hindsight-waiting = 聴牌、待ち:
+
 kyoku =
     {$bakaze ->
         [East] 東
@@ -34,6 +78,9 @@ kyoku =
         *[other] {" "}{$honba}本場
     }
 
+metadata-deal-in-risk-header = 放銃リスク 実際 / 必要 (平均)
+metadata-deal-in-value-header = 放銃打点 実際 / 必要 (合計)
+metadata-deal-ins-header = 放銃 (危険打牌の後)
 metadata-engine-header = AI
 metadata-game-length-header = 対局の長さ
 metadata-game-length-value = {$length ->
@@ -43,12 +90,32 @@ metadata-game-length-value = {$length ->
 }
 metadata-generated-at-header = 生成日時
 metadata-header = メタデータ
+metadata-high-risk-header = 危険打牌
+metadata-inference-time-header = 推論時間 (合計 / p50 / p90 / p99 / 最大)
 metadata-loading-time-header = ロード時間
 metadata-log-id-header = ログID
+metadata-loss-header =
+    {$engine ->
+        [Mortal] Q値
+        *[other] pt期待値
+    }損失 (合計 / 平均)
 metadata-match-rate-header = AI一致率
+metadata-max-batch-size-header = 最大バッチサイズ
+metadata-mean-prob-header = 実際の選択の平均確率
+metadata-mistakes-header = 加重ミス
 metadata-mjai-reviewer-version-header = mjai-reviewerバージョン
+metadata-model-tag-header = モデルタグ
+metadata-non-greedy-header = 非貪欲な判断
 metadata-player-id-header = プレイヤーID
+metadata-rating-header = レーティング
 metadata-review-time-header = 検討時間
+metadata-temperature-header = ソフトマックス温度 (τ)
+metadata-tolerance-header = 許容範囲
+metadata-tolerance-prob-ratio = 確率比 ≥ {$ratio}
+metadata-tolerance-q-gap = Q値の差 ≤ {$gap}
+metadata-tolerated-header = 許容数/総数
+metadata-top-k-header = 上位k一致率
+metadata-top-k-value = 上位{$k}
 
 panel-expand = 展開:
 panel-expand-all = 全て
@@ -64,9 +131,23 @@ place-percentage = {$rank}位率 (%)
 player = プレイヤー
 
 replay-viewer = 牌譜ビューア
+show-in-replay-viewer = 牌譜ビューアで表示
+
+safety-against = {$seat}に対して:
+safety-genbutsu = 現物
+safety-header = 安全牌
+safety-honor = 字牌、{$visible}枚見え
+safety-no-chance = ノーチャンス
+safety-one-chance = ワンチャンス
+safety-suji = 筋
+safety-unsafe = 危険
 
 score-header = 点数
 
+severity-blunder = 悪手
+severity-minor = 軽微
+severity-mistake = ミス
+
 tehai-cuts = {$player}打
 tehai-draw = 自摸
 tehai-kans = {$player}加槓
@@ -79,8 +160,17 @@ tenhou-net-6-paste-instruction-after-link = {" "}の EDIT AS TEXT に貼り付
 
 title = 牌譜検討
 
+top-mistakes-header = ワーストミス
+top-mistakes-loss-header =
+    {$engine ->
+        [Mortal] Q値
+        *[other] pt期待値
+    }損失
+top-mistakes-severity-header = 重大度
+
 turn = {$junme}巡目 (残り{$tiles-left})
 turn-info-furiten = (振り聴)
+turn-info-loss = 損失
 turn-info-shanten = {$shanten}向聴
 turn-info-tenpai = 聴牌
 
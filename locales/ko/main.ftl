@@ -10,18 +10,62 @@ action-ron = 론
 action-ryuukyoku = 유국
 action-tsumo = 쯔모
 
+board-dealer = (친)
+board-header = 테이블
+board-info = {$bakaze}{$kyoku}-{$honba}, 공탁 {$kyotaku}, 남은 패 {$tiles-left}장
+board-riichi = 리치
+
+categories-header = 판단 유형
+category-agari = 론/쯔모
+category-call = 치/퐁
+category-decisions-header = 판단 수
+category-defense = 리치 상대 타패
+category-efficiency = 패효율
+category-header = 유형
+category-kan = 깡
+category-kyushukyuhai = 구종구패
+category-loss-header =
+    {$engine ->
+        [Mortal] Q값
+        *[other] pt 기댓값
+    } 손실 (평균)
+category-match-rate-header = 일치율 (%)
+category-matches-header = 일치 수
+category-riichi = 리치 vs 다마
+category-tolerated-header = 허용 수
+
+defense-deal-in-prob-header = 방총률 (%)
+defense-deal-in-value-header = 방총 점수
+defense-discard-header = 타패
+defense-header = 수비
+defense-risky-deal-in = 위험패를 버린 후 방총
+defense-safest-header = 가장 안전한 패
+defense-turn-header = 순
+
 donate-header = 기부
 
 end-status-ron = {$seat}{"\u00a0"}{action-ron} {$delta}
 end-status-ryuukyoku = {action-ryuukyoku}
 end-status-tsumo = {$seat}{"\u00a0"}{action-tsumo} {$delta}
 
+engine-log-dropped = (이전 {$count}줄 생략)
+engine-log-empty = 엔진이 표준 에러에 아무것도 출력하지 않았거나 출력이 캐시되었습니다.
+engine-log-header = 엔진 로그
+
 final-ranking-probs-at-the-start-of-kyoku = {$kyoku} 개국 시점의 최종 순위 예상
 
 game-summary-header = 대국 요약
 
 help-header = 도움말
 
+hindsight-deal-in = 이 타패로 방총하는 상대:
+hindsight-header = 결과론
+hindsight-next-draws = 다음 쯔모:
+hindsight-note = 숨겨진 손패와 이후의 패보로 본 것으로, AI는 보지 못하는 정보입니다.
+hindsight-shanten = {$shanten}샹텐
+hindsight-tenpai = 텐파이
+hindsight-waiting = 텐파이, 대기:
+
 kyoku =
     {$bakaze ->
         [East] 동
@@ -34,17 +78,40 @@ kyoku =
         *[other] {" "}{$honba}본장
     }
 
+metadata-deal-in-risk-header = 방총 위험 실제 / 필요 (평균)
+metadata-deal-in-value-header = 방총 점수 실제 / 필요 (합계)
+metadata-deal-ins-header = 방총 (위험 타패 후)
 metadata-engine-header = AI 엔진
 metadata-game-length-header = 대국 길이
 metadata-game-length-value = {$length}
 metadata-generated-at-header = 생성 시각
 metadata-header = 메타데이터
+metadata-high-risk-header = 위험 타패
+metadata-inference-time-header = 추론 시간 (합계 / p50 / p90 / p99 / 최대)
 metadata-loading-time-header = 로딩 시간
 metadata-log-id-header = 로그 ID
+metadata-loss-header =
+    {$engine ->
+        [Mortal] Q값
+        *[other] pt 기댓값
+    } 손실 (합계 / 평균)
 metadata-match-rate-header = AI 일치율
+metadata-max-batch-size-header = 최대 배치 크기
+metadata-mean-prob-header = 실제 선택의 평균 확률
+metadata-mistakes-header = 가중 실수
 metadata-mjai-reviewer-version-header = mjai-reviewer 버전
+metadata-model-tag-header = 모델 태그
+metadata-non-greedy-header = 비탐욕적 판단
 metadata-player-id-header = 작사 ID
+metadata-rating-header = 레이팅
 metadata-review-time-header = 복기 소요시간
+metadata-temperature-header = 소프트맥스 온도 (τ)
+metadata-tolerance-header = 허용 범위
+metadata-tolerance-prob-ratio = 확률 비 ≥ {$ratio}
+metadata-tolerance-q-gap = Q값 차이 ≤ {$gap}
+metadata-tolerated-header = 허용 수/전체
+metadata-top-k-header = 상위 k 일치율
+metadata-top-k-value = 상위 {$k}
 
 panel-expand = 상세보기:
 panel-expand-all = 모두 펼침
@@ -60,9 +127,23 @@ place-percentage = {$rank}위 확률 (%)
 player = 작사
 
 replay-viewer = 다시보기
+show-in-replay-viewer = 다시보기에서 보기
+
+safety-against = {$seat} 상대:
+safety-genbutsu = 현물
+safety-header = 안전패
+safety-honor = 자패, {$visible}장 보임
+safety-no-chance = 노찬스
+safety-one-chance = 원찬스
+safety-suji = 스지
+safety-unsafe = 위험
 
 score-header = 점수
 
+severity-blunder = 악수
+severity-minor = 경미
+severity-mistake = 실수
+
 tehai-cuts = {$player} 타
 tehai-draw = 쯔모
 tehai-kans = {$player} 깡
@@ -75,9 +156,18 @@ tenhou-net-6-paste-instruction-after-link = {" "}- EDIT AS TEXT.
 
 title = AI 복기
 
+top-mistakes-header = 주요 실수
+top-mistakes-loss-header =
+    {$engine ->
+        [Mortal] Q값
+        *[other] pt 기댓값
+    } 손실
+top-mistakes-severity-header = 심각도
+
 turn = {$junme}순 (남은 패 {$tiles-left}장)
 
 turn-info-furiten = (후리텐)
+turn-info-loss = 손실
 turn-info-shanten = {$shanten}샹텐
 turn-info-tenpai = 텐파이
 
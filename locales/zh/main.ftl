@@ -10,18 +10,62 @@ action-ron = 和
 action-ryuukyoku = 流局
 action-tsumo = 自摸
 
+board-dealer = (庄家)
+board-header = 牌桌
+board-info = {$bakaze}{$kyoku}-{$honba}，供托 {$kyotaku}，余 {$tiles-left} 张
+board-riichi = 立直
+
+categories-header = 决策类别
+category-agari = 和/自摸
+category-call = 吃/碰
+category-decisions-header = 决策数
+category-defense = 面对立直的打牌
+category-efficiency = 牌效
+category-header = 类别
+category-kan = 杠
+category-kyushukyuhai = 九种九牌
+category-loss-header =
+    {$engine ->
+        [Mortal] Q 值
+        *[other] pt 期望值
+    }损失 (平均)
+category-match-rate-header = 一致率 (%)
+category-matches-header = 一致数
+category-riichi = 立直还是默听
+category-tolerated-header = 容许数
+
+defense-deal-in-prob-header = 放铳率 (%)
+defense-deal-in-value-header = 放铳打点
+defense-discard-header = 打牌
+defense-header = 防守
+defense-risky-deal-in = 打出危险牌后放铳
+defense-safest-header = 最安全的牌
+defense-turn-header = 巡目
+
 donate-header = 打赏
 
 end-status-ron = {$seat}{"\u00a0"}荣和 {$delta}
 end-status-ryuukyoku = {action-ryuukyoku}
 end-status-tsumo = {$seat}{"\u00a0"}{action-tsumo} {$delta}
 
+engine-log-dropped = (省略了之前的 {$count} 行)
+engine-log-empty = 引擎没有向标准错误输出任何内容，或其输出来自缓存。
+engine-log-header = 引擎日志
+
 final-ranking-probs-at-the-start-of-kyoku = {$kyoku}开局时的最终顺位概率
 
 game-summary-header = 目录
 
 help-header = 帮助
 
+hindsight-deal-in = 此打牌会放铳给:
+hindsight-header = 事后复盘
+hindsight-next-draws = 之后的摸牌:
+hindsight-note = 根据隐藏的手牌和之后的牌谱，AI 看不到这些信息。
+hindsight-shanten = {$shanten}向听
+hindsight-tenpai = 听牌
+hindsight-waiting = 听牌，听:
+
 kyoku =
     {$bakaze ->
         [East] 东
@@ -34,6 +78,9 @@ kyoku =
         *[other] {" "}{$honba}本场
     }
 
+metadata-deal-in-risk-header = 放铳风险 实际 / 必要 (平均)
+metadata-deal-in-value-header = 放铳打点 实际 / 必要 (总计)
+metadata-deal-ins-header = 放铳 (危险打牌后)
 metadata-engine-header = AI 引擎
 metadata-game-length-header = 对局长度
 metadata-game-length-value = {$length ->
@@ -43,12 +90,32 @@ metadata-game-length-value = {$length ->
 }
 metadata-generated-at-header = 生成时间
 metadata-header = 元数据
+metadata-high-risk-header = 危险打牌
+metadata-inference-time-header = 推理用时 (总计 / p50 / p90 / p99 / 最大)
 metadata-loading-time-header = 载入用时
 metadata-log-id-header = 牌谱 ID
+metadata-loss-header =
+    {$engine ->
+        [Mortal] Q 值
+        *[other] pt 期望值
+    }损失 (总计 / 平均)
 metadata-match-rate-header = AI 一致率
+metadata-max-batch-size-header = 最大批大小
+metadata-mean-prob-header = 实际选择的平均概率
+metadata-mistakes-header = 加权失误
 metadata-mjai-reviewer-version-header = mjai-reviewer 版本
+metadata-model-tag-header = 模型标签
+metadata-non-greedy-header = 非贪婪决策
 metadata-player-id-header = 玩家 ID
+metadata-rating-header = 评分
 metadata-review-time-header = 检讨用时
+metadata-temperature-header = softmax 温度 (τ)
+metadata-tolerance-header = 容差
+metadata-tolerance-prob-ratio = 概率比 ≥ {$ratio}
+metadata-tolerance-q-gap = Q 值差 ≤ {$gap}
+metadata-tolerated-header = 容许数/总数
+metadata-top-k-header = 前 k 一致率
+metadata-top-k-value = 前 {$k}
 
 panel-expand = 展开:
 panel-expand-all = 全部
@@ -64,9 +131,23 @@ place-percentage = {$rank}位率 (%)
 player = 玩家
 
 replay-viewer = 牌谱回放
+show-in-replay-viewer = 在牌谱回放中查看
+
+safety-against = 针对{$seat}:
+safety-genbutsu = 现物
+safety-header = 安全牌
+safety-honor = 字牌，可见 {$visible} 张
+safety-no-chance = 壁
+safety-one-chance = 单壁
+safety-suji = 筋
+safety-unsafe = 危险
 
 score-header = 点数
 
+severity-blunder = 恶手
+severity-minor = 轻微
+severity-mistake = 失误
+
 tehai-cuts = {$player}打
 tehai-draw = 自摸
 tehai-kans = {$player}加杠
@@ -79,8 +160,17 @@ tenhou-net-6-paste-instruction-after-link = {" "}的「EDIT AS TEXT」选项里
 
 title = 牌谱检讨
 
+top-mistakes-header = 主要失误
+top-mistakes-loss-header =
+    {$engine ->
+        [Mortal] Q 值
+        *[other] pt 期望值
+    }损失
+top-mistakes-severity-header = 严重程度
+
 turn = {$junme}巡 (余{$tiles-left})
 turn-info-furiten = (振听)
+turn-info-loss = 损失
 turn-info-shanten = {$shanten}向听
 turn-info-tenpai = 听牌
 
@@ -0,0 +1,264 @@
+use crate::log;
+use crate::opts::{CacheAction, Engine};
+use crate::review::transcript::Transcript;
+use convlog::Event;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde_json as json;
+
+const SUFFIX: &str = ".transcript";
+
+/// An on-disk cache of engine transcripts.
+///
+/// Only what the engine sees goes into the key, so a hit can be replayed
+/// through the current reviewer with any rendering or post-processing options
+/// (temperature, deviation threshold, language, etc.).
+pub struct Cache {
+    dir: PathBuf,
+}
+
+pub struct Key {
+    engine: Engine,
+    hash: String,
+}
+
+#[derive(Debug, Default)]
+pub struct PruneStats {
+    pub removed: usize,
+    pub removed_bytes: u64,
+    pub kept: usize,
+    pub kept_bytes: u64,
+}
+
+/// Runs the `cache` subcommand.
+pub fn run(cache_dir: Option<PathBuf>, action: CacheAction) -> Result<()> {
+    let cache = Cache::new(cache_dir)?;
+    match action {
+        CacheAction::Dir => println!("{}", cache.dir().display()),
+        CacheAction::Stat => {
+            let (count, size) = cache.stat()?;
+            println!(
+                "{count} entries, {} in {}",
+                format_size(size),
+                cache.dir().display()
+            );
+        }
+        CacheAction::Prune {
+            older_than,
+            max_size,
+        } => {
+            let stats = cache.prune(older_than, max_size)?;
+            log!(
+                "removed {} entries ({}), kept {} entries ({})",
+                stats.removed,
+                format_size(stats.removed_bytes),
+                stats.kept,
+                format_size(stats.kept_bytes),
+            );
+        }
+        CacheAction::Clear => {
+            let stats = cache.clear()?;
+            log!(
+                "removed {} entries ({})",
+                stats.removed,
+                format_size(stats.removed_bytes),
+            );
+        }
+    }
+    Ok(())
+}
+
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = size as f64;
+    let mut unit = 0;
+    while size >= 1024. && unit < UNITS.len() - 1 {
+        size /= 1024.;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{size} {}", UNITS[unit])
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+impl Key {
+    /// `exe` is identified by its path, size and modification time, `config`
+    /// by its content. `config` is Mortal's config.toml or akochan's tactics
+    /// file. For Mortal, the model file that config.toml points to is
+    /// identified the same way as `exe`.
+    pub fn new(
+        engine: Engine,
+        events: &[Event],
+        player_id: u8,
+        exe: &Path,
+        config: &Path,
+    ) -> Result<Self> {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(b"mjai-reviewer transcript v1\n");
+        hasher.update(format!("{engine}\n{player_id}\n").as_bytes());
+
+        hash_file_meta(&mut hasher, exe)
+            .with_context(|| format!("failed to stat engine executable {}", exe.display()))?;
+
+        let config_content = fs::read(config)
+            .with_context(|| format!("failed to read engine config {}", config.display()))?;
+        hasher.update(&config_content);
+        hasher.update(b"\n");
+
+        if engine == Engine::Mortal
+            && let Some(model) = mortal_model(&config_content)
+        {
+            hash_file_meta(&mut hasher, &model)
+                .with_context(|| format!("failed to stat Mortal model {}", model.display()))?;
+        }
+
+        for event in events {
+            hasher.update(json::to_string(event)?.as_bytes());
+            hasher.update(b"\n");
+        }
+
+        Ok(Self {
+            engine,
+            hash: hasher.finalize().to_hex().to_string(),
+        })
+    }
+}
+
+fn hash_file_meta(hasher: &mut blake3::Hasher, path: &Path) -> Result<()> {
+    let meta = fs::metadata(path)?;
+    let mtime = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    hasher.update(format!("{}\n{}\n{}\n", path.display(), meta.len(), mtime.as_nanos()).as_bytes());
+    Ok(())
+}
+
+/// Returns `control.state_file` of Mortal's config.toml, which is the model
+/// file. A relative path is relative to the working directory, the same as
+/// Mortal resolves it.
+fn mortal_model(config: &[u8]) -> Option<PathBuf> {
+    let config: toml::Table = toml::from_slice(config).ok()?;
+    let path = config.get("control")?.get("state_file")?.as_str()?;
+    Some(PathBuf::from(path))
+}
+
+impl Cache {
+    /// Uses `dir`, or the platform's cache directory if it is `None`.
+    pub fn new(dir: Option<PathBuf>) -> Result<Self> {
+        let dir = match dir {
+            Some(d) => d,
+            None => dirs::cache_dir()
+                .context("failed to locate cache directory, try --cache-dir")?
+                .join("mjai-reviewer"),
+        };
+        Ok(Self { dir })
+    }
+
+    #[inline]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path_of(&self, key: &Key) -> PathBuf {
+        self.dir
+            .join(format!("{}-{}{SUFFIX}", key.engine, key.hash))
+    }
+
+    pub fn load(&self, key: &Key) -> Result<Option<Transcript>> {
+        let path = self.path_of(key);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let transcript = Transcript::load(&path)?;
+        // refresh mtime so that prune works as LRU
+        File::options()
+            .append(true)
+            .open(&path)
+            .and_then(|f| f.set_modified(SystemTime::now()))
+            .ok();
+        Ok(Some(transcript))
+    }
+
+    pub fn store(&self, key: &Key, transcript: &Transcript) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("failed to create cache dir {}", self.dir.display()))?;
+        let path = self.path_of(key);
+        let tmp_path = path.with_extension("tmp");
+        transcript.save(&tmp_path)?;
+        fs::rename(&tmp_path, &path)
+            .with_context(|| format!("failed to write cache file {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Returns the number and total size of entries.
+    pub fn stat(&self) -> Result<(usize, u64)> {
+        let entries = self.entries()?;
+        let size = entries.iter().map(|(_, _, size)| size).sum();
+        Ok((entries.len(), size))
+    }
+
+    /// Removes entries not used for `older_than`, then the least recently
+    /// used ones until the total size is within `max_size` bytes.
+    pub fn prune(&self, older_than: Option<Duration>, max_size: Option<u64>) -> Result<PruneStats> {
+        let mut stats = PruneStats::default();
+        let mut entries = self.entries()?;
+        // most recently used first
+        entries.sort_by(|(_, l, _), (_, r, _)| r.cmp(l));
+
+        let now = SystemTime::now();
+        for (path, mtime, size) in entries {
+            let too_old =
+                older_than.is_some_and(|d| now.duration_since(mtime).unwrap_or_default() > d);
+            let too_big = max_size.is_some_and(|m| stats.kept_bytes + size > m);
+            if too_old || too_big {
+                fs::remove_file(&path)
+                    .with_context(|| format!("failed to remove {}", path.display()))?;
+                stats.removed += 1;
+                stats.removed_bytes += size;
+            } else {
+                stats.kept += 1;
+                stats.kept_bytes += size;
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Removes all entries.
+    pub fn clear(&self) -> Result<PruneStats> {
+        let mut stats = PruneStats::default();
+        for (path, _, size) in self.entries()? {
+            fs::remove_file(&path)
+                .with_context(|| format!("failed to remove {}", path.display()))?;
+            stats.removed += 1;
+            stats.removed_bytes += size;
+        }
+        Ok(stats)
+    }
+
+    fn entries(&self) -> Result<Vec<(PathBuf, SystemTime, u64)>> {
+        if !self.dir.is_dir() {
+            return Ok(vec![]);
+        }
+        let mut ret = vec![];
+        let read_dir = fs::read_dir(&self.dir)
+            .with_context(|| format!("failed to read cache dir {}", self.dir.display()))?;
+        for entry in read_dir {
+            let entry = entry?;
+            let path = entry.path();
+            if !path.to_string_lossy().ends_with(SUFFIX) {
+                continue;
+            }
+            let meta = entry.metadata()?;
+            let mtime = meta.modified().unwrap_or(UNIX_EPOCH);
+            ret.push((path, mtime, meta.len()));
+        }
+        Ok(ret)
+    }
+}
@@ -0,0 +1,256 @@
+use crate::opts::Category;
+use crate::render::compact_tiles;
+use crate::review::metrics::Severity;
+use convlog::Event;
+use std::io::prelude::*;
+
+use anyhow::Result;
+use serde::Serialize;
+
+/// One review entry, flattened for spreadsheets and dataframes.
+#[derive(Debug, Clone, Serialize)]
+pub struct Row {
+    pub game_id: Option<String>,
+    pub seat: u8,
+    /// Like E1 and S4.
+    pub kyoku: String,
+    pub honba: u8,
+    pub junme: u8,
+    pub tiles_left: u8,
+    pub category: Category,
+    pub severity: Option<Severity>,
+    /// Mortal only.
+    pub shanten: Option<i8>,
+    /// Mortal only.
+    pub at_furiten: Option<bool>,
+    pub actual: String,
+    pub expected: String,
+    pub is_equal: bool,
+    pub is_tolerable: bool,
+    /// Q-value for Mortal and pt EV for akochan.
+    pub actual_value: Option<f64>,
+    pub expected_value: Option<f64>,
+    /// The gap between the two values.
+    pub loss: Option<f64>,
+}
+
+pub fn kyoku_name(kyoku: u8) -> String {
+    let bakaze = ["E", "S", "W", "N"][kyoku as usize / 4 % 4];
+    format!("{bakaze}{}", kyoku % 4 + 1)
+}
+
+/// Writes actions like "dahai 3m" and "pon 5m (50m)", joined with ", ".
+pub fn describe(actions: &[Event]) -> String {
+    actions
+        .iter()
+        .map(|ev| {
+            let value = serde_json::to_value(ev).unwrap_or_default();
+            let mut s = value["type"].as_str().unwrap_or_default().to_owned();
+            if let Event::Dahai { pai, .. }
+            | Event::Chi { pai, .. }
+            | Event::Pon { pai, .. }
+            | Event::Daiminkan { pai, .. }
+            | Event::Kakan { pai, .. } = *ev
+            {
+                s.push(' ');
+                s.push_str(&compact_tiles(&[pai]));
+            }
+            let consumed = match ev {
+                Event::Chi { consumed, .. } | Event::Pon { consumed, .. } => &consumed[..],
+                Event::Daiminkan { consumed, .. } | Event::Kakan { consumed, .. } => consumed,
+                Event::Ankan { consumed, .. } => consumed,
+                _ => &[],
+            };
+            if !consumed.is_empty() {
+                s.push_str(" (");
+                s.push_str(&compact_tiles(consumed));
+                s.push(')');
+            }
+            s
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn write_csv<W>(rows: &[Row], w: W) -> Result<()>
+where
+    W: Write,
+{
+    let mut writer = csv::Writer::from_writer(w);
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(feature = "parquet")]
+pub fn write_parquet<W>(rows: &[Row], w: W) -> Result<()>
+where
+    W: Write + Send,
+{
+    use anyhow::Context;
+    use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DataType, DoubleType, Int32Type};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::{SerializedFileWriter, SerializedRowGroupWriter};
+    use parquet::schema::parser::parse_message_type;
+    use std::sync::Arc;
+
+    /// In the same order as the fields of `Row`.
+    const SCHEMA: &str = "
+        message review {
+            OPTIONAL BYTE_ARRAY game_id (UTF8);
+            REQUIRED INT32 seat;
+            REQUIRED BYTE_ARRAY kyoku (UTF8);
+            REQUIRED INT32 honba;
+            REQUIRED INT32 junme;
+            REQUIRED INT32 tiles_left;
+            REQUIRED BYTE_ARRAY category (UTF8);
+            OPTIONAL BYTE_ARRAY severity (UTF8);
+            OPTIONAL INT32 shanten;
+            OPTIONAL BOOLEAN at_furiten;
+            REQUIRED BYTE_ARRAY actual (UTF8);
+            REQUIRED BYTE_ARRAY expected (UTF8);
+            REQUIRED BOOLEAN is_equal;
+            REQUIRED BOOLEAN is_tolerable;
+            OPTIONAL DOUBLE actual_value;
+            OPTIONAL DOUBLE expected_value;
+            OPTIONAL DOUBLE loss;
+        }
+    ";
+
+    /// Writes the next column, which is optional if `values` are.
+    fn column<T, W>(
+        group: &mut SerializedRowGroupWriter<'_, W>,
+        optional: bool,
+        values: impl Iterator<Item = Option<T::T>>,
+    ) -> Result<()>
+    where
+        T: DataType,
+        W: Write + Send,
+    {
+        let mut writer = group.next_column()?.context("too few columns")?;
+        let (values, defs): (Vec<_>, Vec<_>) = values
+            .map(|v| {
+                let def = i16::from(v.is_some());
+                (v, def)
+            })
+            .unzip();
+        let values: Vec<_> = values.into_iter().flatten().collect();
+        writer
+            .typed::<T>()
+            .write_batch(&values, optional.then_some(&defs), None)?;
+        writer.close()?;
+        Ok(())
+    }
+
+    fn name<T: Serialize>(v: &T) -> ByteArray {
+        let value = serde_json::to_value(v).unwrap_or_default();
+        ByteArray::from(value.as_str().unwrap_or_default())
+    }
+    let text = |s: &str| Some(ByteArray::from(s));
+
+    let schema = Arc::new(parse_message_type(SCHEMA)?);
+    let props = Arc::new(WriterProperties::builder().build());
+    let mut writer = SerializedFileWriter::new(w, schema, props)?;
+    let mut group = writer.next_row_group()?;
+    let g = &mut group;
+    let rows = || rows.iter();
+
+    column::<ByteArrayType, _>(
+        g,
+        true,
+        rows().map(|r| r.game_id.as_deref().map(ByteArray::from)),
+    )?;
+    column::<Int32Type, _>(g, false, rows().map(|r| Some(r.seat.into())))?;
+    column::<ByteArrayType, _>(g, false, rows().map(|r| text(&r.kyoku)))?;
+    column::<Int32Type, _>(g, false, rows().map(|r| Some(r.honba.into())))?;
+    column::<Int32Type, _>(g, false, rows().map(|r| Some(r.junme.into())))?;
+    column::<Int32Type, _>(g, false, rows().map(|r| Some(r.tiles_left.into())))?;
+    column::<ByteArrayType, _>(g, false, rows().map(|r| Some(name(&r.category))))?;
+    column::<ByteArrayType, _>(g, true, rows().map(|r| r.severity.as_ref().map(name)))?;
+    column::<Int32Type, _>(g, true, rows().map(|r| r.shanten.map(i32::from)))?;
+    column::<BoolType, _>(g, true, rows().map(|r| r.at_furiten))?;
+    column::<ByteArrayType, _>(g, false, rows().map(|r| text(&r.actual)))?;
+    column::<ByteArrayType, _>(g, false, rows().map(|r| text(&r.expected)))?;
+    column::<BoolType, _>(g, false, rows().map(|r| Some(r.is_equal)))?;
+    column::<BoolType, _>(g, false, rows().map(|r| Some(r.is_tolerable)))?;
+    column::<DoubleType, _>(g, true, rows().map(|r| r.actual_value))?;
+    column::<DoubleType, _>(g, true, rows().map(|r| r.expected_value))?;
+    column::<DoubleType, _>(g, true, rows().map(|r| r.loss))?;
+
+    group.close()?;
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json as json;
+
+    fn rows() -> Vec<Row> {
+        let actions: Vec<Event> = json::from_str(
+            r#"[{"type":"chi","actor":0,"target":3,"pai":"5mr","consumed":["4m","6m"]},{"type":"dahai","actor":0,"pai":"E","tsumogiri":false}]"#,
+        )
+        .unwrap();
+        let row = Row {
+            game_id: Some("2019050417gm-0029-0000-4f2a8622".to_owned()),
+            seat: 0,
+            kyoku: kyoku_name(5),
+            honba: 1,
+            junme: 3,
+            tiles_left: 60,
+            category: Category::Call,
+            severity: Some(Severity::Mistake),
+            shanten: None,
+            at_furiten: None,
+            actual: describe(&actions[..1]),
+            expected: describe(&actions),
+            is_equal: false,
+            is_tolerable: false,
+            actual_value: Some(0.5),
+            expected_value: None,
+            loss: None,
+        };
+        vec![row.clone(), Row { seat: 2, ..row }]
+    }
+
+    #[test]
+    fn csv() {
+        let mut out = vec![];
+        write_csv(&rows(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("game_id,seat,kyoku,honba,"));
+        assert_eq!(
+            lines[1],
+            r#"2019050417gm-0029-0000-4f2a8622,0,S2,1,3,60,call,mistake,,,chi 0m (46m),"chi 0m (46m), dahai 1z",false,false,0.5,,"#,
+        );
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+        use parquet::record::Field;
+
+        let mut file = tempfile::tempfile().unwrap();
+        write_parquet(&rows(), &mut file).unwrap();
+        let reader = SerializedFileReader::new(file).unwrap();
+        let read: Vec<_> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(read.len(), 2);
+        let columns: Vec<_> = read[1].get_column_iter().collect();
+        assert_eq!(columns.len(), 17);
+        assert_eq!(*columns[1].0, "seat");
+        assert_eq!(*columns[1].1, Field::Int(2));
+        assert_eq!(*columns[7].1, Field::Str("mistake".to_owned()));
+        assert_eq!(*columns[8].1, Field::Null);
+        assert_eq!(*columns[14].1, Field::Double(0.5));
+    }
+}
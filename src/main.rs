@@ -70,13 +70,12 @@
     clippy::string_add,
     clippy::string_add_assign,
     clippy::string_lit_as_bytes,
-    clippy::string_to_string,
     clippy::suboptimal_flops,
     clippy::suspicious_to_owned,
     clippy::trait_duplication_in_bounds,
     clippy::trivially_copy_pass_by_ref,
     clippy::type_repetition_in_bounds,
-    clippy::unchecked_duration_subtraction,
+    clippy::unchecked_time_subtraction,
     clippy::undocumented_unsafe_blocks,
     clippy::unicode_not_nfc,
     clippy::uninlined_format_args,
@@ -92,10 +91,13 @@
     clippy::useless_let_if_seq
 )]
 
+mod cache;
 mod download;
+mod export;
 mod log;
 mod log_source;
 mod opts;
+mod position;
 mod render;
 mod review;
 mod softmax;
@@ -103,10 +105,20 @@ mod state;
 mod tactics;
 mod tehai;
 
+use crate::cache::{Cache, Key};
 use crate::log_source::LogSource;
-use crate::opts::{AkochanOptions, Engine, InputOptions, MortalOptions, Options, OutputOptions};
-use crate::render::View;
+use crate::opts::{
+    AkochanOptions, Engine, EngineOptions, Format, InputOptions, Metric, MortalOptions, Options,
+    OutputOptions, RenderOptions, SubCommand,
+};
+use crate::render::{FORMAT_VERSION, View};
+use crate::review::metrics::Thresholds;
+use crate::review::mortal::Tolerance;
+use crate::review::process::{self, EngineLog, Watchdog};
+use crate::review::session::Session;
+use crate::review::transcript::Transcript;
 use crate::review::{Review, akochan, mortal};
+use crate::tactics::{Overrides, TacticsJson};
 use chrono::SubsecRound;
 use convlog::tenhou::{GameLength, Log, RawLog};
 use convlog::tenhou_to_mjai;
@@ -114,6 +126,7 @@ use std::fs::File;
 use std::io;
 use std::io::prelude::*;
 use std::path::{Path, PathBuf};
+use std::process::Command;
 
 use anyhow::{Context, Result, bail, ensure};
 use clap::{Parser, ValueEnum};
@@ -143,9 +156,14 @@ enum ReportOutput {
 
 fn main() -> Result<()> {
     let Options {
+        command,
         player_id,
         player_name,
         kyokus,
+        only,
+        mistake_threshold,
+        blunder_threshold,
+        top_mistakes,
         no_review,
         verbose,
         engine,
@@ -161,26 +179,57 @@ fn main() -> Result<()> {
                 tenhou_out,
                 mjai_out,
                 json,
+                format,
+                mut metrics,
                 show_rating,
+                layout,
                 without_log_viewer,
+                offline,
                 anonymous,
                 no_open,
                 lang,
             },
+        engine_opts:
+            EngineOptions {
+                watchdog_opts,
+                no_cache,
+                cache_dir,
+                record_transcript,
+                replay_transcript,
+                diagnostics,
+            },
         mortal_opts:
             MortalOptions {
                 mortal_exe,
                 mortal_cfg,
                 temperature,
+                tolerance_q,
+                tolerance_prob,
             },
         akochan_opts:
             AkochanOptions {
                 akochan_dir,
                 akochan_tactics,
+                tactics_preset,
+                jun_pt,
+                tactic,
                 deviation_threshold,
             },
     } = Options::parse();
 
+    if let Some(SubCommand::Cache { cache_dir, action }) = command {
+        return cache::run(cache_dir, action);
+    }
+    if let Some(SubCommand::Position(opts)) = command {
+        return position::run(*opts);
+    }
+    if let Some(SubCommand::Tactics { action }) = command {
+        return tactics::run(action);
+    }
+    if let Some(SubCommand::Render(opts)) = command {
+        return render_saved(*opts);
+    }
+
     // sometimes the log URL contains the actor info
     let mut player_id_opt = player_id;
 
@@ -267,20 +316,20 @@ fn main() -> Result<()> {
         }
     };
 
-    if player_id_opt.is_none() {
-        if let Some(player_name) = player_name {
-            let names = raw_log.get_names();
-            for (idx, n) in names.iter().enumerate() {
-                if *n == player_name {
-                    player_id_opt = Some(idx as u8);
-                    break;
-                }
+    if player_id_opt.is_none()
+        && let Some(player_name) = player_name
+    {
+        let names = raw_log.get_names();
+        for (idx, n) in names.iter().enumerate() {
+            if *n == player_name {
+                player_id_opt = Some(idx as u8);
+                break;
             }
-            ensure!(
-                player_id_opt.is_some(),
-                "there is no player named {player_name}, available players: {names:?}",
-            );
         }
+        ensure!(
+            player_id_opt.is_some(),
+            "there is no player named {player_name}, available players: {names:?}",
+        );
     }
 
     // apply filters
@@ -293,14 +342,14 @@ fn main() -> Result<()> {
         ensure!(!raw_log.is_empty(), "no kyoku to review (invalid filter?)");
     }
 
-    // clone the parsed raw log for possible reuse (split)
-    let cloned_raw_log;
-    let split_raw_logs = if without_log_viewer {
-        None
-    } else {
-        cloned_raw_log = raw_log.clone();
-        Some(cloned_raw_log.split_by_kyoku())
-    };
+    // split the parsed raw log by kyoku for the log viewer
+    let split_raw_logs = (!without_log_viewer).then(|| {
+        raw_log
+            .split_by_kyoku()
+            .into_iter()
+            .map(RawLog::from)
+            .collect()
+    });
 
     // convert from RawLog to Log.
     let log = Log::try_from(raw_log).context("invalid log")?;
@@ -345,29 +394,113 @@ fn main() -> Result<()> {
     log!("players: {}", log.names.join(", "));
     log!("target: {} ({player_id})", log.names[player_id as usize]);
 
+    process::install_interrupt_handler()?;
+    let watchdog = watchdog_opts.watchdog();
+    let cache = if no_cache {
+        None
+    } else {
+        Some(Cache::new(cache_dir)?)
+    };
+
+    // with a transcript to replay, the engine does not have to exist at all
+    let replay = replay_transcript
+        .map(|path| Transcript::load(&path))
+        .transpose()?;
+    let replaying = replay.is_some();
+    let runner = EngineRunner {
+        replay,
+        cache: cache.as_ref(),
+        watchdog,
+        verbose,
+    };
+
+    let default_thresholds = match engine {
+        Engine::Mortal => Thresholds::MORTAL,
+        Engine::Akochan => Thresholds::AKOCHAN,
+    };
+    let thresholds = Thresholds {
+        mistake: mistake_threshold.unwrap_or(default_thresholds.mistake),
+        blunder: blunder_threshold.unwrap_or(default_thresholds.blunder),
+    };
+
     let begin_review = chrono::Local::now();
-    let review = match engine {
+    let mut effective_tactics = None;
+    let (mut review, transcript, engine_log) = match engine {
         Engine::Mortal => {
-            let mortal_exe = canonicalize!(mortal_exe)?;
-            let mortal_cfg = canonicalize!(mortal_cfg)?;
+            let mortal_exe = if replaying {
+                mortal_exe
+            } else {
+                canonicalize!(mortal_exe)?
+            };
+            let mortal_cfg = if replaying {
+                mortal_cfg
+            } else {
+                canonicalize!(mortal_cfg)?
+            };
+            let tolerance = match (tolerance_q, tolerance_prob) {
+                (Some(gap), _) => Some(Tolerance::QGap(gap)),
+                (_, Some(ratio)) => Some(Tolerance::ProbRatio(ratio)),
+                _ => None,
+            };
             let reviewer = mortal::Reviewer {
                 mortal_exe: &mortal_exe,
                 mortal_cfg: &mortal_cfg,
                 events: &events,
                 player_id,
                 temperature,
+                tolerance,
+                only: &only,
+                thresholds,
+                top_mistakes,
                 verbose,
             };
-            let result = reviewer.review().context("failed to review")?;
-            Review::Mortal(result)
+            let (result, transcript, engine_log) = runner
+                .run(
+                    || Key::new(engine, &events, player_id, &mortal_exe, &mortal_cfg),
+                    reviewer.command(),
+                    |s| reviewer.review(s),
+                )
+                .context("failed to review")?;
+            (Review::Mortal(result), transcript, engine_log)
         }
         Engine::Akochan => {
             let akochan_exe: PathBuf = [&akochan_dir, Path::new("system.exe")]
                 .into_iter()
                 .collect();
-            let akochan_dir = canonicalize!(akochan_dir)?;
-            let akochan_exe = canonicalize!(akochan_exe)?;
-            let akochan_tactics = canonicalize!(akochan_tactics)?;
+            let akochan_dir = if replaying {
+                akochan_dir
+            } else {
+                canonicalize!(akochan_dir)?
+            };
+            let akochan_exe = if replaying {
+                akochan_exe
+            } else {
+                canonicalize!(akochan_exe)?
+            };
+            // the merged file is deleted when dropped at the end of the review
+            let (akochan_tactics, _merged_tactics_file) = if replaying {
+                (akochan_tactics, None)
+            } else {
+                let akochan_tactics = canonicalize!(akochan_tactics)?;
+                let mut tactics_json = TacticsJson::load(&akochan_tactics)?;
+                let overrides = Overrides {
+                    preset: tactics_preset,
+                    jun_pt,
+                    fields: &tactic,
+                };
+                let ret = if overrides.is_empty() {
+                    (akochan_tactics, None)
+                } else {
+                    tactics_json.apply(&overrides)?;
+                    let file = tactics_json.write_temp()?;
+                    if verbose {
+                        log!("merged tactics written to {}", file.path().display());
+                    }
+                    (file.path().to_owned(), Some(file))
+                };
+                effective_tactics = Some(tactics_json.tactics);
+                ret
+            };
             let reviewer = akochan::Reviewer {
                 akochan_exe: &akochan_exe,
                 akochan_dir: &akochan_dir,
@@ -375,12 +508,40 @@ fn main() -> Result<()> {
                 events: &events,
                 player_id,
                 deviation_threshold,
+                only: &only,
+                thresholds,
+                top_mistakes,
                 verbose,
             };
-            let result = reviewer.review().context("failed to review")?;
-            Review::Akochan(result)
+            let (result, transcript, engine_log) = runner
+                .run(
+                    || Key::new(engine, &events, player_id, &akochan_exe, &akochan_tactics),
+                    reviewer.command(),
+                    |s| reviewer.review(s),
+                )
+                .context("failed to review")?;
+            (Review::Akochan(result), transcript, engine_log)
         }
     };
+    if !only.is_empty() && review.total_reviewed() == 0 {
+        log!("WARNING: none of the decisions of the player fall in the categories of --only");
+    }
+
+    if let Some(path) = record_transcript {
+        transcript.save(&path)?;
+        log!("engine transcript saved to {}", path.display());
+    }
+    if let Some(path) = diagnostics {
+        engine_log.save(&path)?;
+        log!("engine diagnostics saved to {}", path.display());
+    }
+
+    if show_rating && !metrics.contains(&Metric::Rating) {
+        metrics.push(Metric::Rating);
+    }
+    review.retain_metrics(&metrics);
+
+    let format = if json { Format::Json } else { format };
 
     // determine output file
     let out = if let Some(filename) = out_file {
@@ -390,25 +551,14 @@ fn main() -> Result<()> {
             ReportOutput::File(filename)
         }
     } else {
-        let suffix = if json { ".json" } else { ".html" };
         let mut filename = log_source
             .default_output_filename(engine, player_id)
             .into_os_string();
-        filename.push(suffix);
+        filename.push(".");
+        filename.push(format.extension());
         ReportOutput::File(PathBuf::from(filename))
     };
 
-    // prepare output, can be a file or stdout
-    let mut out_write: Box<dyn Write> = match &out {
-        ReportOutput::File(filename) => {
-            let file = File::create(filename).with_context(|| {
-                format!("failed to create output report file {}", filename.display())
-            })?;
-            Box::new(file)
-        }
-        ReportOutput::Stdout => Box::new(io::stdout()),
-    };
-
     let now = chrono::Local::now();
     let loading_time =
         (begin_review.trunc_subsecs(3) - begin_convert_log.trunc_subsecs(3)).to_std()?;
@@ -417,42 +567,181 @@ fn main() -> Result<()> {
 
     // render the HTML report page or JSON
     let view = View {
-        engine,
+        format_version: FORMAT_VERSION,
         game_length: log.game_length,
-        log_id: if anonymous { None } else { log_source.log_id() },
+        log_id: if anonymous {
+            None
+        } else {
+            log_source.log_id().map(str::to_owned)
+        },
         loading_time,
         review_time,
-        show_rating,
-        version: env!("CARGO_PKG_VERSION"),
+        metrics,
+        version: env!("CARGO_PKG_VERSION").to_owned(),
 
         review,
         player_id,
+        tactics: effective_tactics,
+        engine_log: verbose.then_some(engine_log),
 
-        split_logs: split_raw_logs.as_deref(),
-        mjai_log: &events,
-        lang: lang_value.get_name(),
+        split_logs: split_raw_logs,
+        mjai_log: events,
+        lang: lang_value.get_name().to_owned(),
+        layout,
+        offline,
     };
-    log!("writing output...");
-    if json {
-        json::to_writer(&mut out_write, &view).context("failed to write JSON result")?;
+    write_report(&view, format, out, no_open)
+}
+
+/// Renders a review saved in JSON with the options of the output changed.
+fn render_saved(opts: RenderOptions) -> Result<()> {
+    let RenderOptions {
+        in_file,
+        out_file,
+        format,
+        lang,
+        layout,
+        show_rating,
+        offline,
+        anonymous,
+        no_open,
+    } = opts;
+
+    let from_stdin = in_file == Path::new("-");
+    let mut view = if from_stdin {
+        View::from_json(io::stdin().lock())
     } else {
-        view.render(&mut out_write)
-            .context("failed to render HTML report")?;
+        let file = File::open(&in_file)
+            .with_context(|| format!("failed to open review file {}", in_file.display()))?;
+        View::from_json(io::BufReader::new(file))
+    }
+    .context("failed to load the saved review")?;
+
+    if let Some(lang) = lang {
+        view.lang = lang.to_possible_value().unwrap().get_name().to_owned();
+    }
+    if let Some(layout) = layout {
+        view.layout = layout;
+    }
+    if show_rating && !view.metrics.contains(&Metric::Rating) {
+        ensure!(
+            view.review.rating().is_some(),
+            "the review has no rating, review the log again with --show-rating to include it",
+        );
+        view.metrics.push(Metric::Rating);
+    }
+    view.offline |= offline;
+    if anonymous {
+        view.anonymize();
     }
+
+    let out = match out_file {
+        Some(filename) if filename == Path::new("-") => ReportOutput::Stdout,
+        Some(filename) => ReportOutput::File(filename),
+        None if from_stdin => ReportOutput::Stdout,
+        None => {
+            let filename = in_file.with_extension(format.extension());
+            ensure!(
+                filename != in_file,
+                "refusing to overwrite the input, specify the output with --out-file",
+            );
+            ReportOutput::File(filename)
+        }
+    };
+    write_report(&view, format, out, no_open)
+}
+
+/// Writes `view` in `format` to `out`, and opens it if it is an HTML file.
+fn write_report(view: &View, format: Format, out: ReportOutput, no_open: bool) -> Result<()> {
+    // prepare output, can be a file or stdout
+    let mut out_write: Box<dyn Write + Send> = match &out {
+        ReportOutput::File(filename) => {
+            let file = File::create(filename).with_context(|| {
+                format!("failed to create output report file {}", filename.display())
+            })?;
+            Box::new(file)
+        }
+        ReportOutput::Stdout => Box::new(io::stdout()),
+    };
+
+    log!("writing output...");
+    view.write(&mut out_write, format)?;
     out_write.flush().context("failed to flush output")?;
     log!("complete");
 
     // open the output page
-    if !json && !no_open {
-        if let ReportOutput::File(filepath) = out {
-            opener::open(&filepath).with_context(|| {
-                format!(
-                    "failed to open rendered HTML report file {}",
-                    filepath.display(),
-                )
-            })?;
-        }
+    if format == Format::Html
+        && !no_open
+        && let ReportOutput::File(filepath) = out
+    {
+        opener::open(&filepath).with_context(|| {
+            format!(
+                "failed to open rendered HTML report file {}",
+                filepath.display(),
+            )
+        })?;
     }
 
     Ok(())
 }
+
+/// Decides where the engine side of a review comes from.
+struct EngineRunner<'a> {
+    /// Replaces the engine entirely if present.
+    replay: Option<Transcript>,
+    cache: Option<&'a Cache>,
+    watchdog: Watchdog,
+    verbose: bool,
+}
+
+impl EngineRunner<'_> {
+    /// Runs `review` against, in order of preference, the transcript to
+    /// replay, the cached output of the engine, or a newly spawned engine
+    /// whose output then gets cached. Returns the result along with the
+    /// transcript it ran against and what the engine wrote to stderr, if it
+    /// was run.
+    fn run<T>(
+        self,
+        key: impl FnOnce() -> Result<Key>,
+        command: Command,
+        mut review: impl FnMut(&mut Session) -> Result<T>,
+    ) -> Result<(T, Transcript, EngineLog)> {
+        if let Some(transcript) = self.replay {
+            log!("replaying engine transcript...");
+            let mut session = Session::replay(transcript);
+            let result = review(&mut session)?;
+            let (transcript, engine_log) = session.finish()?;
+            return Ok((result, transcript, engine_log));
+        }
+
+        let cache = self.cache.map(|c| key().map(|k| (c, k))).transpose()?;
+        if let Some((cache, key)) = &cache {
+            match cache.load(key) {
+                Ok(Some(transcript)) => {
+                    log!("using cached engine output from {}", cache.dir().display());
+                    let mut session = Session::replay(transcript);
+                    let result = review(&mut session).and_then(|r| {
+                        let (transcript, engine_log) = session.finish()?;
+                        Ok((r, transcript, engine_log))
+                    });
+                    match result {
+                        Ok(r) => return Ok(r),
+                        Err(err) => log!("WARNING: ignoring stale cache: {err:#}"),
+                    }
+                }
+                Ok(None) => (),
+                Err(err) => log!("WARNING: ignoring broken cache: {err:#}"),
+            }
+        }
+
+        let mut session = Session::spawn(command, self.watchdog, self.verbose)?;
+        let result = review(&mut session)?;
+        let (transcript, engine_log) = session.finish()?;
+        if let Some((cache, key)) = &cache
+            && let Err(err) = cache.store(key, &transcript)
+        {
+            log!("WARNING: failed to write cache: {err:#}");
+        }
+        Ok((result, transcript, engine_log))
+    }
+}
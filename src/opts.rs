@@ -1,9 +1,12 @@
+use crate::review::process::Watchdog;
 use std::ffi::OsStr;
 use std::fmt;
 use std::path::PathBuf;
+use std::time::Duration;
 
-use clap::{ArgGroup, Args, Parser, ValueEnum};
-use serde::Serialize;
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+use serde_json::{self as json, Value};
 use url::Url;
 
 const ABOUT: &str = r#"🔍🀄️ Review your Tenhou or Mahjong Soul (Jantama) log with mjai-compatible mahjong AIs.
@@ -15,11 +18,15 @@ For more details, please visit the repo at <https://github.com/Equim-chan/mjai-r
 
 #[derive(Debug, Parser)]
 #[clap(version, about = ABOUT)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 #[clap(group(
     ArgGroup::new("input-methods")
         .args(&["in_file", "tenhou_id", "url"]),
 ))]
 pub struct Options {
+    #[clap(subcommand)]
+    pub command: Option<SubCommand>,
+
     /// The ID of the player to review, which is a number within 0-3. It is the
     /// number after "&tw=" in Tenhou's log URL, namely, the player sitting at
     /// the East at E1 is 0, and his shimocha (right) will be 1, toimen (across)
@@ -44,11 +51,33 @@ pub struct Options {
     #[clap(short, long, value_name = "LIST")]
     pub kyokus: Option<String>,
 
+    /// Categories of decisions to review, separated by commas. If not
+    /// specified, review all of them.
+    #[clap(long, value_name = "LIST", value_enum, value_delimiter = ',')]
+    pub only: Vec<Category>,
+
+    /// Decisions different from the engine's choice with a loss of at least
+    /// LOSS are mistakes rather than minor ones. The loss is the gap of
+    /// Q-values for Mortal, defaults to 0.05, and the gap of pt EV for
+    /// akochan, defaults to 1.
+    #[clap(long, value_name = "LOSS")]
+    pub mistake_threshold: Option<f64>,
+
+    /// Decisions with a loss of at least LOSS are blunders. Defaults to 0.2
+    /// for Mortal and 5 for akochan.
+    #[clap(long, value_name = "LOSS")]
+    pub blunder_threshold: Option<f64>,
+
+    /// Number of the worst decisions to list at the top of the report.
+    #[clap(long, value_name = "N", default_value = "5")]
+    pub top_mistakes: usize,
+
     /// Do not review at all, but only download and save files.
     #[clap(long)]
     pub no_review: bool,
 
-    /// Print verbose logs.
+    /// Print verbose logs, including what the engine writes to stderr, which
+    /// is also shown in the report.
     #[clap(short, long)]
     pub verbose: bool,
 
@@ -62,6 +91,9 @@ pub struct Options {
     )]
     pub engine: Option<Engine>,
 
+    #[clap(flatten, next_help_heading = "Engine Options")]
+    pub engine_opts: EngineOptions,
+
     #[clap(flatten, next_help_heading = "Mortal Options")]
     pub mortal_opts: MortalOptions,
 
@@ -69,6 +101,194 @@ pub struct Options {
     pub akochan_opts: AkochanOptions,
 }
 
+#[derive(Debug, Subcommand)]
+pub enum SubCommand {
+    /// Manage the cache of engine outputs.
+    Cache {
+        /// The directory of the cache. Defaults to "mjai-reviewer" under the
+        /// cache directory of the platform.
+        #[clap(long, value_name = "DIR")]
+        cache_dir: Option<PathBuf>,
+
+        #[clap(subcommand)]
+        action: CacheAction,
+    },
+
+    /// Ask Mortal about a single position described on the command line,
+    /// without a full log.
+    ///
+    /// Tiles are written in the compact form like "123m406p789s1122z", where
+    /// "0" is a red five and "1z" to "7z" are East, South, West, North, White,
+    /// Green and Red. A minimal sequence of events leading to the position is
+    /// made up, in which the opponents discard terminals and honors that are
+    /// not otherwise visible.
+    Position(Box<PositionOptions>),
+
+    /// Work with tactics files of akochan.
+    Tactics {
+        #[clap(subcommand)]
+        action: TacticsAction,
+    },
+
+    /// Render a review saved by --json again, without running the engine.
+    Render(Box<RenderOptions>),
+}
+
+#[derive(Debug, Args)]
+#[clap(group(
+    ArgGroup::new("decision")
+        .args(&["tsumo", "discarded"])
+        .required(true),
+))]
+pub struct PositionOptions {
+    /// The closed part of the hand, without the tile just drawn.
+    #[clap(long, value_name = "TILES")]
+    pub hand: String,
+
+    /// Decide what to do after drawing TILE.
+    #[clap(long, value_name = "TILE")]
+    pub tsumo: Option<String>,
+
+    /// Decide whether to call or ron TILE discarded by the player sitting at
+    /// SEAT, for example "3:5p".
+    #[clap(long, value_name = "SEAT:TILE")]
+    pub discarded: Option<String>,
+
+    /// A meld, in the order they were made. KIND is one of "chi", "pon",
+    /// "kan" and "ankan", and the first of TILES is the one called, for
+    /// example "chi:546m".
+    #[clap(long = "meld", value_name = "KIND:TILES")]
+    pub melds: Vec<String>,
+
+    /// The discards of the player in order, including the ones made right
+    /// after the melds. The turn is the number of discards plus one.
+    #[clap(long, value_name = "TILES", default_value = "")]
+    pub discards: String,
+
+    /// Dora indicators, one plus one for every kan.
+    #[clap(long, value_name = "TILES")]
+    pub dora: String,
+
+    /// The round, for example "E1" or "S3".
+    #[clap(long, default_value = "E1")]
+    pub round: String,
+
+    #[clap(long, default_value = "0")]
+    pub honba: u8,
+
+    /// The number of riichi sticks on the table.
+    #[clap(long, default_value = "0")]
+    pub kyotaku: u8,
+
+    /// Scores of player 0 to 3, separated by commas.
+    #[clap(long, value_name = "LIST", default_value = "25000,25000,25000,25000")]
+    pub scores: String,
+
+    /// The seat of the player, which is a number within 0-3, the same as
+    /// --player-id of a review. The dealer of E1 sits at 0.
+    #[clap(short = 'a', long, value_name = "ID", default_value = "0", value_parser = parse_player_id)]
+    pub player_id: u8,
+
+    /// Output the result in JSON.
+    #[clap(long)]
+    pub json: bool,
+
+    /// Save the made up mjai format log to FILE. If FILE is "-", write to
+    /// stdout.
+    #[clap(long, value_name = "FILE")]
+    pub mjai_out: Option<PathBuf>,
+
+    /// Print verbose logs.
+    #[clap(short, long)]
+    pub verbose: bool,
+
+    #[clap(flatten, next_help_heading = "Engine Options")]
+    pub watchdog_opts: WatchdogOptions,
+
+    #[clap(flatten, next_help_heading = "Mortal Options")]
+    pub mortal_opts: MortalOptions,
+}
+
+#[derive(Debug, Args)]
+pub struct RenderOptions {
+    /// The review written by --json or "--format json". If FILE is "-", read
+    /// from stdin.
+    #[clap(value_name = "FILE")]
+    pub in_file: PathBuf,
+
+    /// Save the output to FILE. If FILE is "-", write to stdout. Defaults to
+    /// the name of the input with the extension of --format, or stdout if the
+    /// input is stdin.
+    #[clap(short, long, value_name = "FILE")]
+    pub out_file: Option<PathBuf>,
+
+    /// The format of the output.
+    #[clap(long, value_enum, default_value_t = Format::Html)]
+    pub format: Format,
+
+    /// The language to use for the report. Defaults to the one of the review.
+    #[clap(long)]
+    pub lang: Option<Language>,
+
+    /// The initial layout of the HTML report. Defaults to the one of the
+    /// review.
+    #[clap(long, value_enum)]
+    pub layout: Option<Layout>,
+
+    /// Include rating, which is only in reviews saved with --show-rating.
+    #[clap(long)]
+    pub show_rating: bool,
+
+    /// Make the HTML report self-contained, the same as --offline of a
+    /// review.
+    #[clap(long)]
+    pub offline: bool,
+
+    /// Hide player names and the log ID.
+    #[clap(long)]
+    pub anonymous: bool,
+
+    /// Do not automatically open the output file in browser.
+    #[clap(long)]
+    pub no_open: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CacheAction {
+    /// Print the directory of the cache.
+    Dir,
+
+    /// Print the number and total size of cached entries.
+    Stat,
+
+    /// Remove old entries.
+    Prune {
+        /// Remove entries not used within DURATION, for example "30days".
+        #[clap(long, value_name = "DURATION", value_parser = parse_duration)]
+        older_than: Option<Duration>,
+
+        /// Remove least recently used entries until the cache is no larger
+        /// than SIZE, for example "500M".
+        #[clap(long, value_name = "SIZE", value_parser = parse_size)]
+        max_size: Option<u64>,
+    },
+
+    /// Remove all entries.
+    Clear,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TacticsAction {
+    /// Validate a tactics file without running a review.
+    Check {
+        #[clap(
+            value_name = "FILE",
+            default_value_os = OsStr::new("./akochan/tactics.json")
+        )]
+        file: PathBuf,
+    },
+}
+
 #[derive(Debug, Args)]
 pub struct InputOptions {
     /// The name of a tenhou.net/6 format log file to input. If FILE is "-" or
@@ -105,22 +325,47 @@ pub struct OutputOptions {
     #[clap(long, value_name = "FILE")]
     pub mjai_out: Option<PathBuf>,
 
-    /// Output review result in JSON instead of HTML.
-    #[clap(long)]
+    /// Output review result in JSON instead of HTML, the same as
+    /// "--format json". It can be rendered again by the render subcommand.
+    #[clap(long, conflicts_with = "format")]
     pub json: bool,
 
-    /// The language to use for the generated HTML report.
+    /// The format of the output.
+    #[clap(long, value_enum, default_value_t = Format::Html)]
+    pub format: Format,
+
+    /// The language to use for the generated report.
     #[clap(long, default_value = "en")]
     pub lang: Language,
 
-    /// Whether or not to include rating.
+    /// Metrics to include in the report and JSON, separated by commas.
+    #[clap(
+        long,
+        value_name = "LIST",
+        value_enum,
+        value_delimiter = ',',
+        default_value = "match-rate,loss,top-k"
+    )]
+    pub metrics: Vec<Metric>,
+
+    /// Include rating, the same as adding "rating" to --metrics.
     #[clap(long)]
     pub show_rating: bool,
 
+    /// The initial layout of the generated HTML report, which can be switched
+    /// in the page.
+    #[clap(long, value_enum, default_value_t = Layout::Vertical)]
+    pub layout: Layout,
+
     /// Do not include log viewer in the generated HTML report.
     #[clap(long)]
     pub without_log_viewer: bool,
 
+    /// Make the generated HTML report self-contained, so that it makes no
+    /// network requests. Math is rendered in MathML instead of KaTeX.
+    #[clap(long)]
+    pub offline: bool,
+
     /// Do not include player names in the generated HTML report.
     #[clap(long)]
     pub anonymous: bool,
@@ -130,6 +375,116 @@ pub struct OutputOptions {
     pub no_open: bool,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// HTML report.
+    Html,
+    /// The whole review result.
+    Json,
+    /// Plain text report, for terminals.
+    Text,
+    /// Markdown report, for issues and chats.
+    Markdown,
+    /// One row for each entry.
+    Csv,
+    /// The same as csv, in Parquet.
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+/// The part of [`EngineOptions`] that also applies to the position command.
+#[derive(Debug, Args)]
+pub struct WatchdogOptions {
+    /// Give up if the engine does not respond to a single event within
+    /// DURATION. "0s" means no limit.
+    #[clap(long, value_name = "DURATION", default_value = "120s", value_parser = parse_duration)]
+    pub engine_timeout: Duration,
+
+    /// Give up if the whole review does not finish within DURATION.
+    #[clap(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub review_timeout: Option<Duration>,
+
+    /// Restart the engine up to N times if it crashes or times out, replaying
+    /// the events sent to it so far.
+    #[clap(long, value_name = "N", default_value = "0")]
+    pub engine_retries: u32,
+}
+
+impl WatchdogOptions {
+    pub fn watchdog(&self) -> Watchdog {
+        let &Self {
+            engine_timeout,
+            review_timeout,
+            engine_retries,
+        } = self;
+        Watchdog {
+            response_timeout: (!engine_timeout.is_zero()).then_some(engine_timeout),
+            total_timeout: review_timeout,
+            retries: engine_retries,
+        }
+    }
+}
+
+impl Format {
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Html => "html",
+            Self::Json => "json",
+            Self::Text => "txt",
+            Self::Markdown => "md",
+            Self::Csv => "csv",
+            #[cfg(feature = "parquet")]
+            Self::Parquet => "parquet",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Layout {
+    /// The log viewer of each kyoku above its entries.
+    Vertical,
+    /// The log viewer of each kyoku beside its entries.
+    Horizontal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Metric {
+    /// The ratio of decisions matching the engine's choice. Mortal only.
+    MatchRate,
+    /// The squared mean of normalized values of the actual actions.
+    Rating,
+    /// Total and mean loss of value versus the best action.
+    Loss,
+    /// Mean probability given to the actual action. Mortal only.
+    Prob,
+    /// How often the actual action is among the top 1, 2 and 3 actions.
+    TopK,
+    /// Number of mistakes, weighted by their losses.
+    Mistakes,
+}
+
+/// The kind of a decision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Category {
+    /// Choosing a discard.
+    Efficiency,
+    /// Choosing a discard while any opponent is in riichi.
+    Defense,
+    /// Riichi or dama, and the discard to declare riichi with.
+    Riichi,
+    /// Chi or pon, or not.
+    Call,
+    /// Kan or not.
+    Kan,
+    /// Ron or tsumo, or not.
+    Agari,
+    /// Abortive draw by nine terminals or not.
+    Kyushukyuhai,
+}
+
 #[derive(Debug, Clone, Serialize, ValueEnum)]
 pub enum Language {
     #[clap(name = "en")]
@@ -153,6 +508,40 @@ pub enum Language {
     KoKR,
 }
 
+#[derive(Debug, Args)]
+pub struct EngineOptions {
+    #[clap(flatten)]
+    pub watchdog_opts: WatchdogOptions,
+
+    /// Always run the engine, neither reading from nor writing to the cache.
+    /// Without this, the output of the engine is cached and reused when the
+    /// same log is reviewed again for the same player with the same engine
+    /// executable and config.
+    #[clap(long)]
+    pub no_cache: bool,
+
+    /// The directory of the cache. Defaults to "mjai-reviewer" under the
+    /// cache directory of the platform.
+    #[clap(long, value_name = "DIR")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Save every line written to and read from the engine to FILE.
+    #[clap(long, value_name = "FILE")]
+    pub record_transcript: Option<PathBuf>,
+
+    /// Do not run the engine, but replay its side from FILE saved by
+    /// --record-transcript instead. The log, player and engine must be the
+    /// same as when it was recorded.
+    #[clap(long, value_name = "FILE", conflicts_with = "record_transcript")]
+    pub replay_transcript: Option<PathBuf>,
+
+    /// Save what the engine writes to stderr to FILE, each line prefixed with
+    /// the index of the event in flight. Nothing is written by the engine when
+    /// its output is cached or replayed.
+    #[clap(long, value_name = "FILE")]
+    pub diagnostics: Option<PathBuf>,
+}
+
 #[derive(Debug, Args)]
 pub struct MortalOptions {
     #[clap(
@@ -173,6 +562,18 @@ pub struct MortalOptions {
     /// not change action orders or ratings.
     #[clap(long, value_name = "TEMP", default_value = "0.1", value_parser = parse_temperature)]
     pub temperature: f32,
+
+    /// Tolerate a decision different from Mortal's if its Q-value is lower
+    /// than the best one by at most GAP. Tolerated decisions are counted apart
+    /// from both matches and mistakes.
+    #[clap(long, value_name = "GAP", value_parser = parse_tolerance_q, conflicts_with = "tolerance_prob")]
+    pub tolerance_q: Option<f64>,
+
+    /// Like --tolerance-q, but tolerate it if its probability is at least RATIO
+    /// times the best one's. Unlike elsewhere, the softmax temperature matters
+    /// here.
+    #[clap(long, value_name = "RATIO", value_parser = parse_tolerance_prob)]
+    pub tolerance_prob: Option<f64>,
 }
 
 #[derive(Debug, Args)]
@@ -191,11 +592,42 @@ pub struct AkochanOptions {
     )]
     pub akochan_tactics: PathBuf,
 
+    /// Use the rank points of a preset in place of `jun_pt` in the tactics
+    /// file.
+    #[clap(long, value_name = "PRESET", value_enum)]
+    pub tactics_preset: Option<TacticsPreset>,
+
+    /// Use these rank points in place of `jun_pt`, overriding --tactics-preset.
+    #[clap(long, value_name = "PT,PT,PT,PT", allow_hyphen_values = true, value_parser = parse_jun_pt)]
+    pub jun_pt: Option<[i32; 4]>,
+
+    /// Set a field of the tactics, where VALUE is JSON or else a string, for
+    /// example `do_kan_ordinary=true`. It overrides the others and can be
+    /// given multiple times.
+    #[clap(long, value_name = "KEY=VALUE", value_parser = parse_tactic)]
+    pub tactic: Vec<(String, Value)>,
+
     #[clap(long, value_name = "DEV", default_value = "0.05")]
     pub deviation_threshold: f64,
 }
 
+/// Rank points of popular rulesets, as `jun_pt` of akochan.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum TacticsPreset {
+    /// Tenhou Houou room, at 8-dan
+    TenhouHouou,
+    /// Tenhou Tokujou room, at 7-dan
+    TenhouTokujou,
+    /// Mahjong Soul Jade room, at Master 1
+    MjsoulJade,
+    /// Mahjong Soul Throne room, at Saint 1
+    MjsoulThrone,
+    /// M.League, with uma and oka
+    MLeague,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
 pub enum Engine {
     Mortal,
     Akochan,
@@ -229,6 +661,59 @@ fn parse_temperature(s: &str) -> Result<f32, String> {
     }
 }
 
+fn parse_tolerance_q(s: &str) -> Result<f64, String> {
+    let v = s.parse::<f64>().map_err(|e| e.to_string())?;
+    if v < 0. {
+        Err(format!("{s} is negative"))
+    } else {
+        Ok(v)
+    }
+}
+
+fn parse_tolerance_prob(s: &str) -> Result<f64, String> {
+    let v = s.parse::<f64>().map_err(|e| e.to_string())?;
+    if v <= 0. || v > 1. {
+        Err(format!("{s} is not within (0, 1]"))
+    } else {
+        Ok(v)
+    }
+}
+
+fn parse_jun_pt(s: &str) -> Result<[i32; 4], String> {
+    let pts = s
+        .split(',')
+        .map(|p| p.trim().parse::<i32>().map_err(|e| format!("{p}: {e}")))
+        .collect::<Result<Vec<_>, _>>()?;
+    pts.try_into()
+        .map_err(|_| format!("{s} does not consist of exactly 4 points"))
+}
+
+fn parse_tactic(s: &str) -> Result<(String, Value), String> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("{s} is not in the form of KEY=VALUE"))?;
+    let value = json::from_str(value).unwrap_or_else(|_| Value::String(value.to_owned()));
+    Ok((key.trim().to_owned(), value))
+}
+
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    humantime::parse_duration(s).map_err(|e| e.to_string())
+}
+
+/// Parses sizes like "1048576", "512K", "100M" and "2G", in powers of 1024.
+fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (num, shift) = match s.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&s[..i], 10),
+        Some((i, 'm' | 'M')) => (&s[..i], 20),
+        Some((i, 'g' | 'G')) => (&s[..i], 30),
+        _ => (s, 0),
+    };
+    let n = num.trim().parse::<u64>().map_err(|e| e.to_string())?;
+    n.checked_mul(1 << shift)
+        .ok_or_else(|| format!("{s} is too large"))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -239,4 +724,14 @@ mod test {
     fn cli_parse() {
         Options::command().debug_assert();
     }
+
+    #[test]
+    fn size_parse() {
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("512K"), Ok(512 << 10));
+        assert_eq!(parse_size("100m"), Ok(100 << 20));
+        assert_eq!(parse_size("2G"), Ok(2 << 30));
+        parse_size("G").unwrap_err();
+        parse_size("1T").unwrap_err();
+    }
 }
@@ -0,0 +1,771 @@
+//! The `position` subcommand, which asks Mortal about a single position
+//! without a full log.
+
+use crate::log;
+use crate::opts::{MortalOptions, PositionOptions};
+use crate::review::metrics::Thresholds;
+use crate::review::mortal::{self, Evaluation};
+use crate::review::process;
+use crate::review::session::Session;
+use convlog::{Event, Tile, t};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use std::{fmt, io};
+
+use anyhow::{Context, Result, bail, ensure};
+use serde_json as json;
+
+/// Number of tiles that can be drawn in a kyoku, excluding rinshan.
+const TOTAL_TSUMOS: u8 = 70;
+
+/// Preferred discards of the made up opponents, from the least to the most
+/// likely to interact with the hand.
+const FILLER_ORDER: [Tile; 34] = [
+    t!(E),
+    t!(S),
+    t!(W),
+    t!(N),
+    t!(P),
+    t!(F),
+    t!(C),
+    t!(1m),
+    t!(9m),
+    t!(1p),
+    t!(9p),
+    t!(1s),
+    t!(9s),
+    t!(2m),
+    t!(8m),
+    t!(2p),
+    t!(8p),
+    t!(2s),
+    t!(8s),
+    t!(3m),
+    t!(7m),
+    t!(3p),
+    t!(7p),
+    t!(3s),
+    t!(7s),
+    t!(4m),
+    t!(6m),
+    t!(4p),
+    t!(6p),
+    t!(4s),
+    t!(6s),
+    t!(5m),
+    t!(5p),
+    t!(5s),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MeldKind {
+    Chi,
+    Pon,
+    Kan,
+    Ankan,
+}
+
+#[derive(Debug, Clone)]
+struct Meld {
+    kind: MeldKind,
+    /// The first one is the tile called, except for ankan.
+    tiles: Vec<Tile>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Decision {
+    Tsumo(Tile),
+    Discarded { seat: u8, tile: Tile },
+}
+
+#[derive(Debug)]
+struct Position {
+    player_id: u8,
+    bakaze: Tile,
+    /// Counts from 1.
+    kyoku: u8,
+    honba: u8,
+    kyotaku: u8,
+    scores: [i32; 4],
+    hand: Vec<Tile>,
+    melds: Vec<Meld>,
+    discards: Vec<Tile>,
+    dora: Vec<Tile>,
+    decision: Decision,
+}
+
+/// What the player does in one of its turns before the decision.
+#[derive(Debug, Clone, Copy)]
+enum Step<'a> {
+    /// Draw and discard the same tile.
+    Tsumogiri(Tile),
+    /// Call a discard of kamicha, or make an ankan, then discard.
+    Meld(&'a Meld, Tile),
+}
+
+pub fn run(opts: PositionOptions) -> Result<()> {
+    let PositionOptions {
+        json,
+        mjai_out,
+        verbose,
+        watchdog_opts,
+        mortal_opts:
+            MortalOptions {
+                mortal_exe,
+                mortal_cfg,
+                temperature,
+                ..
+            },
+        ..
+    } = &opts;
+
+    let position = Position::parse(&opts).context("invalid position")?;
+    let (events, tiles_left) = position
+        .events()
+        .context("failed to make up events for the position")?;
+
+    if let Some(mjai_out) = mjai_out {
+        let mut w: Box<dyn Write> = if mjai_out == Path::new("-") {
+            Box::from(io::stdout())
+        } else {
+            let file = File::create(mjai_out).with_context(|| {
+                format!("failed to create mjai out file {}", mjai_out.display())
+            })?;
+            Box::from(file)
+        };
+        for event in &events {
+            let to_write = json::to_string(event).context("failed to serialize")?;
+            writeln!(w, "{to_write}").with_context(|| {
+                format!("failed to write to mjai out file {}", mjai_out.display())
+            })?;
+        }
+    }
+
+    let mortal_exe = dunce::canonicalize(mortal_exe).with_context(|| {
+        format!(
+            "failed to canonicalize mortal_exe: \"{}\" (does it exist?)",
+            mortal_exe.display(),
+        )
+    })?;
+    let mortal_cfg = dunce::canonicalize(mortal_cfg).with_context(|| {
+        format!(
+            "failed to canonicalize mortal_cfg: \"{}\" (does it exist?)",
+            mortal_cfg.display(),
+        )
+    })?;
+    let reviewer = mortal::Reviewer {
+        mortal_exe: &mortal_exe,
+        mortal_cfg: &mortal_cfg,
+        events: &events,
+        player_id: position.player_id,
+        temperature: *temperature,
+        tolerance: None,
+        only: &[],
+        thresholds: Thresholds::MORTAL,
+        top_mistakes: 0,
+        verbose: *verbose,
+    };
+
+    process::install_interrupt_handler()?;
+    let watchdog = watchdog_opts.watchdog();
+    log!("evaluating the position...");
+    let mut session = Session::spawn(reviewer.command(), watchdog, *verbose)?;
+    let evaluation = reviewer
+        .evaluate_last(&mut session)
+        .context("failed to evaluate the position")?;
+    session.finish()?;
+
+    let mut stdout = io::stdout().lock();
+    if *json {
+        json::to_writer(&mut stdout, &evaluation).context("failed to write JSON result")?;
+        writeln!(stdout)?;
+    } else {
+        position.print(&mut stdout, &evaluation, tiles_left)?;
+    }
+    Ok(())
+}
+
+impl Position {
+    fn parse(opts: &PositionOptions) -> Result<Self> {
+        let hand = parse_tiles(&opts.hand).context("invalid --hand")?;
+        let melds = opts
+            .melds
+            .iter()
+            .map(|s| parse_meld(s).with_context(|| format!("invalid --meld {s:?}")))
+            .collect::<Result<Vec<_>>>()?;
+        let discards = parse_tiles(&opts.discards).context("invalid --discards")?;
+        let dora = parse_tiles(&opts.dora).context("invalid --dora")?;
+
+        let decision = if let Some(s) = &opts.tsumo {
+            Decision::Tsumo(parse_tile(s).context("invalid --tsumo")?)
+        } else if let Some(s) = &opts.discarded {
+            let (seat, tile) = s
+                .split_once(':')
+                .context("invalid --discarded, expected SEAT:TILE")?;
+            let seat: u8 = seat.parse().context("invalid seat of --discarded")?;
+            ensure!(seat < 4, "seat of --discarded must be within 0-3");
+            ensure!(
+                seat != opts.player_id,
+                "--discarded must be a discard of an opponent",
+            );
+            let tile = parse_tile(tile).context("invalid --discarded")?;
+            Decision::Discarded { seat, tile }
+        } else {
+            // unreachable because of the arg group
+            bail!("either --tsumo or --discarded is required");
+        };
+
+        let mut round = opts.round.chars();
+        let bakaze = match round.next() {
+            Some('E') => t!(E),
+            Some('S') => t!(S),
+            Some('W') => t!(W),
+            _ => bail!("invalid --round {:?}, expected E1-W4", opts.round),
+        };
+        let Ok(kyoku @ 1..=4) = round.as_str().parse() else {
+            bail!("invalid --round {:?}, expected E1-W4", opts.round);
+        };
+
+        let scores: Vec<i32> = opts
+            .scores
+            .split(',')
+            .map(|s| s.trim().parse())
+            .collect::<Result<_, _>>()
+            .context("invalid --scores")?;
+        let scores = scores
+            .try_into()
+            .ok()
+            .context("--scores must have exactly 4 numbers")?;
+
+        let position = Self {
+            player_id: opts.player_id,
+            bakaze,
+            kyoku,
+            honba: opts.honba,
+            kyotaku: opts.kyotaku,
+            scores,
+            hand,
+            melds,
+            discards,
+            dora,
+            decision,
+        };
+        position.validate()?;
+        Ok(position)
+    }
+
+    fn validate(&self) -> Result<()> {
+        ensure!(
+            self.hand.len() + self.melds.len() * 3 == 13,
+            "the hand has {} tiles with {} meld(s), expected {}",
+            self.hand.len(),
+            self.melds.len(),
+            13 - self.melds.len() * 3,
+        );
+        ensure!(
+            self.discards.len() >= self.melds.len(),
+            "there must be a discard after every meld, but there are only {} discard(s) with {} meld(s)",
+            self.discards.len(),
+            self.melds.len(),
+        );
+        let kans = self
+            .melds
+            .iter()
+            .filter(|m| matches!(m.kind, MeldKind::Kan | MeldKind::Ankan))
+            .count();
+        ensure!(
+            self.dora.len() == kans + 1,
+            "expected {} dora indicator(s) with {kans} kan(s), got {}",
+            kans + 1,
+            self.dora.len(),
+        );
+
+        let mut counts = [0_u8; 34];
+        let mut akas = [0_u8; 3];
+        for &tile in self.visible_tiles() {
+            counts[tile.deaka().as_usize()] += 1;
+            if tile.is_aka() {
+                akas[tile.as_usize() - t!(5mr).as_usize()] += 1;
+            }
+        }
+        if let Some(kind) = counts.iter().position(|&c| c > 4) {
+            bail!("there are more than 4 {}", must_tile(kind));
+        }
+        if let Some(suit) = akas.iter().position(|&c| c > 1) {
+            bail!(
+                "there are more than 1 {}",
+                must_tile(t!(5mr).as_usize() + suit)
+            );
+        }
+        Ok(())
+    }
+
+    /// All the tiles known from the description.
+    fn visible_tiles(&self) -> impl Iterator<Item = &Tile> {
+        let decision = match &self.decision {
+            Decision::Tsumo(tile) | Decision::Discarded { tile, .. } => tile,
+        };
+        self.hand
+            .iter()
+            .chain(self.melds.iter().flat_map(|m| &m.tiles))
+            .chain(&self.discards)
+            .chain(&self.dora)
+            .chain([decision])
+    }
+
+    /// Makes up a sequence of events leading to the position, returning them
+    /// along with the number of tiles left in the wall.
+    ///
+    /// The player first plays its turns without a meld by discarding what it
+    /// draws, then makes the melds in order, each followed by one of the last
+    /// discards. Every call is made on a discard of kamicha. The opponents
+    /// draw unknown tiles and discard the tiles least likely to interact with
+    /// the hand.
+    fn events(&self) -> Result<(Vec<Event>, u8)> {
+        let player_id = self.player_id;
+        let oya = self.kyoku - 1;
+        let kamicha = (player_id + 3) % 4;
+
+        let normal_turns = self.discards.len() - self.melds.len();
+        let mut steps: VecDeque<_> = self.discards[..normal_turns]
+            .iter()
+            .map(|&d| Step::Tsumogiri(d))
+            .chain(
+                self.melds
+                    .iter()
+                    .zip(&self.discards[normal_turns..])
+                    .map(|(m, &d)| Step::Meld(m, d)),
+            )
+            .collect();
+
+        // the initial hand, which has every tile in the final hand and melds
+        // except the ones drawn or called afterwards
+        let mut haipai = self.hand.clone();
+        for step in &steps {
+            if let Step::Meld(meld, discard) = *step {
+                match meld.kind {
+                    MeldKind::Chi | MeldKind::Pon => {
+                        haipai.extend(&meld.tiles[1..]);
+                        haipai.push(discard);
+                    }
+                    MeldKind::Kan => haipai.extend(&meld.tiles[1..]),
+                    MeldKind::Ankan => haipai.extend(&meld.tiles[..3]),
+                }
+            }
+        }
+        haipai.sort_unstable();
+        let mut tehais = [[t!(?); 13]; 4];
+        // the length has been checked in `validate`
+        tehais[player_id as usize].copy_from_slice(&haipai);
+
+        let mut fillers = Fillers::new(self);
+        let mut doras = self.dora[1..].iter().copied();
+        let mut events = vec![
+            Event::StartGame {
+                names: Default::default(),
+                kyoku_first: 0,
+                aka_flag: true,
+            },
+            Event::StartKyoku {
+                bakaze: self.bakaze,
+                dora_marker: self.dora[0],
+                kyoku: self.kyoku,
+                honba: self.honba,
+                kyotaku: self.kyotaku,
+                oya,
+                scores: self.scores,
+                tehais,
+            },
+        ];
+        let mut tsumos = 0;
+        let mut draw = |events: &mut Vec<Event>, actor, pai| {
+            ensure!(
+                tsumos < TOTAL_TSUMOS,
+                "the wall runs out before the position"
+            );
+            tsumos += 1;
+            events.push(Event::Tsumo { actor, pai });
+            Ok(())
+        };
+
+        let mut seat = oya;
+        loop {
+            if seat == player_id {
+                match steps.pop_front() {
+                    Some(Step::Tsumogiri(pai)) => {
+                        draw(&mut events, player_id, pai)?;
+                        events.push(Event::Dahai {
+                            actor: player_id,
+                            pai,
+                            tsumogiri: true,
+                        });
+                    }
+                    Some(Step::Meld(meld, pai)) => {
+                        ensure!(
+                            meld.kind == MeldKind::Ankan,
+                            "the dealer must discard at least once before calling",
+                        );
+                        let consumed = meld.tiles[..].try_into()?;
+                        draw(&mut events, player_id, meld.tiles[3])?;
+                        events.push(Event::Ankan {
+                            actor: player_id,
+                            consumed,
+                        });
+                        let dora_marker = doras.next().context("missing dora indicator")?;
+                        events.push(Event::Dora { dora_marker });
+                        draw(&mut events, player_id, pai)?;
+                        events.push(Event::Dahai {
+                            actor: player_id,
+                            pai,
+                            tsumogiri: true,
+                        });
+                    }
+                    None => match self.decision {
+                        Decision::Tsumo(pai) => {
+                            draw(&mut events, player_id, pai)?;
+                            break;
+                        }
+                        Decision::Discarded { seat, .. } => bail!(
+                            "seat {seat} cannot discard before the first turn of the player, try adding a discard",
+                        ),
+                    },
+                }
+                seat = (seat + 1) % 4;
+                continue;
+            }
+
+            if steps.is_empty()
+                && let Decision::Discarded { seat: target, tile } = self.decision
+                && target == seat
+            {
+                draw(&mut events, seat, t!(?))?;
+                events.push(Event::Dahai {
+                    actor: seat,
+                    pai: tile,
+                    tsumogiri: false,
+                });
+                break;
+            }
+
+            draw(&mut events, seat, t!(?))?;
+            let called = match steps.front() {
+                Some(&Step::Meld(meld, discard))
+                    if seat == kamicha && meld.kind != MeldKind::Ankan =>
+                {
+                    Some((meld, discard))
+                }
+                _ => None,
+            };
+            let Some((meld, discard)) = called else {
+                events.push(Event::Dahai {
+                    actor: seat,
+                    pai: fillers.next()?,
+                    tsumogiri: true,
+                });
+                seat = (seat + 1) % 4;
+                continue;
+            };
+
+            steps.pop_front();
+            let pai = meld.tiles[0];
+            events.push(Event::Dahai {
+                actor: seat,
+                pai,
+                tsumogiri: false,
+            });
+            let (actor, target) = (player_id, seat);
+            match meld.kind {
+                MeldKind::Chi | MeldKind::Pon => {
+                    let consumed = meld.tiles[1..].try_into()?;
+                    events.push(if meld.kind == MeldKind::Chi {
+                        Event::Chi {
+                            actor,
+                            target,
+                            pai,
+                            consumed,
+                        }
+                    } else {
+                        Event::Pon {
+                            actor,
+                            target,
+                            pai,
+                            consumed,
+                        }
+                    });
+                    events.push(Event::Dahai {
+                        actor,
+                        pai: discard,
+                        tsumogiri: false,
+                    });
+                }
+                _ => {
+                    let consumed = meld.tiles[1..].try_into()?;
+                    events.push(Event::Daiminkan {
+                        actor,
+                        target,
+                        pai,
+                        consumed,
+                    });
+                    draw(&mut events, actor, discard)?;
+                    let dora_marker = doras.next().context("missing dora indicator")?;
+                    events.push(Event::Dora { dora_marker });
+                    events.push(Event::Dahai {
+                        actor,
+                        pai: discard,
+                        tsumogiri: true,
+                    });
+                }
+            }
+            seat = (player_id + 1) % 4;
+        }
+
+        Ok((events, TOTAL_TSUMOS - tsumos))
+    }
+
+    fn print(&self, w: &mut impl Write, evaluation: &Evaluation, tiles_left: u8) -> Result<()> {
+        let Evaluation {
+            expected,
+            details,
+            shanten,
+            at_furiten,
+            model_tag,
+        } = evaluation;
+
+        let oya = self.kyoku - 1;
+        let dealer = if self.player_id == oya {
+            " (dealer)"
+        } else {
+            ""
+        };
+        writeln!(
+            w,
+            "{}{}-{}, seat {}{dealer}, turn {}, {tiles_left} tiles left",
+            match self.bakaze.as_u8() - t!(E).as_u8() {
+                0 => 'E',
+                1 => 'S',
+                _ => 'W',
+            },
+            self.kyoku,
+            self.honba,
+            self.player_id,
+            self.discards.len() + 1,
+        )?;
+
+        write!(w, "hand: {}", Compact(&self.hand))?;
+        match self.decision {
+            Decision::Tsumo(tile) => write!(w, " + {}", Compact(&[tile]))?,
+            Decision::Discarded { seat, tile } => {
+                write!(w, ", seat {seat} discarded {}", Compact(&[tile]))?;
+            }
+        }
+        for meld in &self.melds {
+            let kind = match meld.kind {
+                MeldKind::Chi => "chi",
+                MeldKind::Pon => "pon",
+                MeldKind::Kan => "kan",
+                MeldKind::Ankan => "ankan",
+            };
+            write!(w, ", {kind} {}", Compact(&meld.tiles))?;
+        }
+        writeln!(w)?;
+        writeln!(w, "dora indicators: {}", Compact(&self.dora))?;
+        let furiten = if *at_furiten { ", furiten" } else { "" };
+        writeln!(w, "shanten: {shanten}{furiten}")?;
+        writeln!(w, "Mortal ({model_tag}): {}", describe(expected))?;
+        writeln!(w)?;
+
+        writeln!(w, "{:>3}  {:<16} {:>8} {:>8}", "#", "action", "Q", "prob")?;
+        for (i, detail) in details.iter().enumerate() {
+            writeln!(
+                w,
+                "{:>3}  {:<16} {:>8.4} {:>7.2}%",
+                i + 1,
+                describe(&detail.action),
+                detail.q_value,
+                detail.prob * 100.,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Picks the discards of the made up opponents, never exceeding 4 copies of a
+/// tile in total.
+struct Fillers {
+    counts: [u8; 34],
+    in_hand: [bool; 34],
+}
+
+impl Fillers {
+    fn new(position: &Position) -> Self {
+        let mut counts = [0; 34];
+        for tile in position.visible_tiles() {
+            counts[tile.deaka().as_usize()] += 1;
+        }
+        let mut in_hand = [false; 34];
+        for tile in position
+            .hand
+            .iter()
+            .chain(position.melds.iter().flat_map(|m| &m.tiles))
+        {
+            in_hand[tile.deaka().as_usize()] = true;
+        }
+        Self { counts, in_hand }
+    }
+
+    fn next(&mut self) -> Result<Tile> {
+        // prefer tiles not in the hand, so that they are less likely to be
+        // called or ronned
+        let tile = FILLER_ORDER
+            .iter()
+            .find(|t| !self.in_hand[t.as_usize()] && self.counts[t.as_usize()] < 4)
+            .or_else(|| FILLER_ORDER.iter().find(|t| self.counts[t.as_usize()] < 4))
+            .copied()
+            .context("no tile left for the opponents to discard")?;
+        self.counts[tile.as_usize()] += 1;
+        Ok(tile)
+    }
+}
+
+fn must_tile(id: usize) -> Tile {
+    // only called with valid IDs
+    Tile::try_from(id).unwrap()
+}
+
+/// Parses tiles in the compact form like "123m406p789s1122z".
+fn parse_tiles(s: &str) -> Result<Vec<Tile>> {
+    let mut tiles = vec![];
+    let mut nums = vec![];
+    for c in s.chars().filter(|c| !c.is_whitespace()) {
+        match c {
+            '0'..='9' => nums.push(c as usize - '0' as usize),
+            'm' | 'p' | 's' | 'z' => {
+                ensure!(!nums.is_empty(), "no number before {c:?} in {s:?}");
+                for n in nums.drain(..) {
+                    let id = match (c, n) {
+                        ('z', 1..=7) => 27 + n - 1,
+                        ('z', _) => bail!("invalid honor {n}z in {s:?}"),
+                        (_, 0) => 34 + "mps".find(c).unwrap_or_default(),
+                        (_, _) => "mps".find(c).unwrap_or_default() * 9 + n - 1,
+                    };
+                    tiles.push(must_tile(id));
+                }
+            }
+            _ => bail!("unexpected {c:?} in {s:?}"),
+        }
+    }
+    ensure!(nums.is_empty(), "missing suit at the end of {s:?}");
+    Ok(tiles)
+}
+
+fn parse_tile(s: &str) -> Result<Tile> {
+    match *parse_tiles(s)? {
+        [tile] => Ok(tile),
+        _ => bail!("expected exactly one tile, got {s:?}"),
+    }
+}
+
+fn parse_meld(s: &str) -> Result<Meld> {
+    let (kind, tiles) = s.split_once(':').context("expected KIND:TILES")?;
+    let kind = match kind {
+        "chi" => MeldKind::Chi,
+        "pon" => MeldKind::Pon,
+        "kan" => MeldKind::Kan,
+        "ankan" => MeldKind::Ankan,
+        _ => bail!("unknown kind {kind:?}, expected one of chi, pon, kan and ankan"),
+    };
+    let tiles = parse_tiles(tiles)?;
+    let len = match kind {
+        MeldKind::Chi | MeldKind::Pon => 3,
+        MeldKind::Kan | MeldKind::Ankan => 4,
+    };
+    ensure!(tiles.len() == len, "expected {len} tiles");
+
+    let mut ids: Vec<_> = tiles.iter().map(|t| t.deaka().as_usize()).collect();
+    ids.sort_unstable();
+    let valid = if kind == MeldKind::Chi {
+        ids[0] < 27 && ids[0] / 9 == ids[2] / 9 && ids[1] == ids[0] + 1 && ids[2] == ids[0] + 2
+    } else {
+        ids.iter().all(|&id| id == ids[0])
+    };
+    ensure!(valid, "not a valid {s}");
+    Ok(Meld { kind, tiles })
+}
+
+/// Displays tiles in the compact form.
+struct Compact<'a>(&'a [Tile]);
+
+impl fmt::Display for Compact<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut suit = None;
+        for tile in self.0 {
+            let id = tile.deaka().as_usize();
+            let (s, n) = if id >= 27 {
+                ('z', id - 27 + 1)
+            } else {
+                (['m', 'p', 's'][id / 9], id % 9 + 1)
+            };
+            if let Some(prev) = suit
+                && prev != s
+            {
+                write!(f, "{prev}")?;
+            }
+            suit = Some(s);
+            let n = if tile.is_aka() { 0 } else { n };
+            write!(f, "{n}")?;
+        }
+        if let Some(s) = suit {
+            write!(f, "{s}")?;
+        }
+        Ok(())
+    }
+}
+
+fn describe(event: &Event) -> String {
+    match *event {
+        Event::Dahai { pai, .. } => format!("discard {}", Compact(&[pai])),
+        Event::Reach { .. } => "riichi".to_owned(),
+        Event::Chi { pai, consumed, .. } => {
+            format!("chi {} with {}", Compact(&[pai]), Compact(&consumed),)
+        }
+        Event::Pon { pai, .. } => format!("pon {}", Compact(&[pai])),
+        Event::Daiminkan { pai, .. } | Event::Kakan { pai, .. } => {
+            format!("kan {}", Compact(&[pai]))
+        }
+        Event::Ankan { consumed, .. } => format!("kan {}", Compact(&consumed[..1])),
+        Event::Hora { actor, target, .. } => {
+            if actor == target {
+                "tsumo".to_owned()
+            } else {
+                "ron".to_owned()
+            }
+        }
+        Event::Ryukyoku { .. } => "ryukyoku".to_owned(),
+        Event::None => "pass".to_owned(),
+        _ => format!("{event:?}"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn compact_tiles() {
+        let tiles = parse_tiles("123m406p789s1122z").unwrap();
+        assert_eq!(tiles.len(), 13);
+        assert_eq!(tiles[4], t!(5pr));
+        assert_eq!(tiles[12], t!(S));
+        assert_eq!(Compact(&tiles).to_string(), "123m406p789s1122z");
+
+        parse_tiles("8z").unwrap_err();
+        parse_tiles("123").unwrap_err();
+        parse_tiles("1x").unwrap_err();
+        parse_meld("chi:546m").unwrap();
+        parse_meld("chi:789m1p").unwrap_err();
+        parse_meld("pon:505p").unwrap();
+        parse_meld("kan:1112z").unwrap_err();
+    }
+}
@@ -1,16 +1,20 @@
-use crate::opts::Engine;
+use crate::export;
+use crate::opts::{Format, Layout, Metric};
 use crate::review::Review;
-use convlog::Event;
-use convlog::tenhou::{GameLength, RawPartialLog};
+use crate::review::process::EngineLog;
+use crate::tactics::Tactics;
+use convlog::tenhou::{GameLength, RawLog};
+use convlog::{Board, Event, Replay, Tile};
 use fluent_templates::FluentLoader;
 use std::collections::HashMap;
 use std::io::prelude::*;
+use std::mem;
 use std::time::Duration;
 
-use anyhow::Result;
+use anyhow::{Context, Result, bail};
 use minify_html::{Cfg, minify};
-use serde::Serialize;
-use serde_json::Value;
+use serde::{Deserialize, Serialize};
+use serde_json::{self as json, Value};
 use serde_with::skip_serializing_none;
 use tera::Tera;
 
@@ -29,6 +33,7 @@ fn build_base_templates() -> Result<Tera> {
     tera.register_function("kyoku_to_bakaze", kyoku_to_bakaze);
     tera.register_function("kyoku_to_kyoku_in_bakaze", kyoku_to_kyoku_in_bakaze);
     tera.register_function("pretty_round", pretty_round);
+    tera.register_filter("compact", compact);
 
     tera.add_raw_templates([
         ("macros.tera", include_str!("../templates/macros.tera")),
@@ -36,48 +41,78 @@ fn build_base_templates() -> Result<Tera> {
         ("report.css", include_str!("../templates/report.css")),
         ("report.js", include_str!("../templates/report.js")),
         ("pai.svg", include_str!("../assets/pai.svg")),
+        (
+            "text_macros.txt",
+            include_str!("../templates/text_macros.txt"),
+        ),
+        ("report.txt", include_str!("../templates/report.txt")),
     ])?;
 
     Ok(tera)
 }
 
+/// Version of the JSON written by `View`, bumped on every change that the
+/// JSON of an older version can no longer be read back.
+pub const FORMAT_VERSION: u32 = 1;
+
 #[skip_serializing_none]
-#[derive(Serialize)]
-pub struct View<'a> {
+#[derive(Serialize, Deserialize)]
+pub struct View {
+    pub format_version: u32,
+
     // metadata
-    pub engine: Engine,
     // pub pt: [i32; 4],
     pub game_length: GameLength,
-    pub log_id: Option<&'a str>,
+    pub log_id: Option<String>,
     #[serde(with = "humantime_serde")]
     pub loading_time: Duration,
     #[serde(with = "humantime_serde")]
     pub review_time: Duration,
-    pub show_rating: bool,
-    pub version: &'a str,
+    pub metrics: Vec<Metric>,
+    pub version: String,
 
-    // review
+    // review, along with the engine
+    #[serde(flatten)]
     pub review: Review,
     pub player_id: u8,
+    /// The tactics akochan reviewed with, after overrides.
+    pub tactics: Option<Tactics>,
+    /// What the engine wrote to stderr, with --verbose only.
+    pub engine_log: Option<EngineLog>,
 
-    pub split_logs: Option<&'a [RawPartialLog<'a>]>,
-    pub mjai_log: &'a [Event],
+    /// The log of each kyoku, in tenhou.net/6 format.
+    pub split_logs: Option<Vec<RawLog>>,
+    pub mjai_log: Vec<Event>,
 
-    pub lang: &'a str,
+    pub lang: String,
+    pub layout: Layout,
+    /// Make no network requests from the HTML report.
+    pub offline: bool,
 }
 
-impl View<'_> {
-    pub fn render<W>(&self, w: &mut W) -> Result<()>
-    where
-        W: Write,
-    {
+/// Number of the engine's candidates listed for each entry in text reports.
+const TOP_CANDIDATES: usize = 5;
+
+impl View {
+    fn templates(&self) -> Result<Tera> {
         let mut templates = build_base_templates()?;
         let lang_id = self.lang.parse()?;
         templates.register_function(
             "fluent",
             FluentLoader::new(&*LOCALES).with_default_lang(lang_id),
         );
-        let ctx = tera::Context::from_serialize(self)?;
+        Ok(templates)
+    }
+
+    pub fn render<W>(&self, w: &mut W) -> Result<()>
+    where
+        W: Write,
+    {
+        let templates = self.templates()?;
+        let mut ctx = tera::Context::from_serialize(self)?;
+        if self.split_logs.is_some() {
+            ctx.insert("replays", &replays(&self.mjai_log)?);
+        }
         let original = templates.render("report.tera", &ctx)?;
 
         let cfg = Cfg {
@@ -91,6 +126,113 @@ impl View<'_> {
         w.write_all(&out)?;
         Ok(())
     }
+
+    /// Renders in plain text, or Markdown if `markdown`, with hands in compact
+    /// notation like 123m406p.
+    pub fn render_text<W>(&self, w: &mut W, markdown: bool) -> Result<()>
+    where
+        W: Write,
+    {
+        let templates = self.templates()?;
+        let mut ctx = tera::Context::from_serialize(self)?;
+        ctx.insert("markdown", &markdown);
+        ctx.insert("top_candidates", &TOP_CANDIDATES);
+        templates.render_to("report.txt", &ctx, w)?;
+        Ok(())
+    }
+
+    /// Writes the view in `format`.
+    pub fn write<W>(&self, w: &mut W, format: Format) -> Result<()>
+    where
+        W: Write + Send,
+    {
+        match format {
+            Format::Html => self.render(w).context("failed to render HTML report")?,
+            Format::Json => json::to_writer(w, self).context("failed to write JSON result")?,
+            Format::Text | Format::Markdown => self
+                .render_text(w, format == Format::Markdown)
+                .context("failed to render text report")?,
+            Format::Csv => {
+                let rows = self.review.rows(self.log_id.as_deref(), self.player_id);
+                export::write_csv(&rows, w).context("failed to write CSV")?;
+            }
+            #[cfg(feature = "parquet")]
+            Format::Parquet => {
+                let rows = self.review.rows(self.log_id.as_deref(), self.player_id);
+                export::write_parquet(&rows, w).context("failed to write Parquet")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads back a view written in JSON, which must be of the current
+    /// `FORMAT_VERSION`.
+    pub fn from_json<R>(r: R) -> Result<Self>
+    where
+        R: Read,
+    {
+        let value: Value = json::from_reader(r).context("failed to parse JSON")?;
+        match value.get("format_version").and_then(Value::as_u64) {
+            Some(v) if v == u64::from(FORMAT_VERSION) => (),
+            Some(v) => bail!(
+                "the review is in format version {v}, but only version {FORMAT_VERSION} is supported, review the log again to upgrade it",
+            ),
+            None => bail!(
+                "the review has no format version, it is either not written by --json or written by an older version of mjai-reviewer",
+            ),
+        }
+        json::from_value(value).context("failed to read the review")
+    }
+
+    /// Hides the player names and the log ID, the same as --anonymous of a
+    /// review.
+    pub fn anonymize(&mut self) {
+        self.log_id = None;
+        for log in self.split_logs.iter_mut().flatten() {
+            log.hide_names();
+        }
+        for event in &mut self.mjai_log {
+            if let Event::StartGame { names, .. } = event {
+                *names = RawLog::anonymous_names();
+            }
+        }
+    }
+}
+
+/// What the replay viewer of a kyoku draws, so that the script in the report
+/// does not have to track the table by itself.
+#[derive(Serialize)]
+struct KyokuReplay<'a> {
+    /// Index into the log of the `start_kyoku` event.
+    first: usize,
+    /// From `start_kyoku` to `end_kyoku`.
+    events: &'a [Event],
+    /// The table after each of `events`.
+    boards: Vec<Board>,
+}
+
+fn replays(events: &[Event]) -> Result<Vec<KyokuReplay<'_>>> {
+    let mut replay = Replay::new(events);
+    let mut ret = vec![];
+    let mut first = 0;
+    let mut boards = vec![];
+    while let Some(board) = replay.step() {
+        let board = board?.clone();
+        let i = replay.position() - 1;
+        if matches!(events[i], Event::StartKyoku { .. }) {
+            first = i;
+            boards.clear();
+        }
+        boards.push(board);
+        if matches!(events[i], Event::EndKyoku) {
+            ret.push(KyokuReplay {
+                first,
+                events: &events[first..=i],
+                boards: mem::take(&mut boards),
+            });
+        }
+    }
+    Ok(ret)
 }
 
 fn kyoku_to_bakaze(args: &HashMap<String, Value>) -> tera::Result<Value> {
@@ -135,13 +277,134 @@ fn pretty_round(args: &HashMap<String, Value>) -> tera::Result<Value> {
     Ok(Value::Array(seps))
 }
 
+/// Writes tiles like 123m406p11z, in which 0 is an aka 5 and ? is unknown.
+pub fn compact_tiles(tiles: &[Tile]) -> String {
+    let mut ret = String::new();
+    let mut suit = None;
+    for tile in tiles {
+        let (num, s) = match tile.as_usize() {
+            id @ 0..27 => (id % 9 + 1, b"mps"[id / 9]),
+            id @ 27..34 => (id - 26, b'z'),
+            id @ 34..37 => (0, b"mps"[id - 34]),
+            _ => {
+                ret.extend(suit.take().map(char::from));
+                ret.push('?');
+                continue;
+            }
+        };
+        if suit.is_some_and(|last| last != s) {
+            ret.extend(suit.map(char::from));
+        }
+        suit = Some(s);
+        ret.push_str(&num.to_string());
+    }
+    ret.extend(suit.map(char::from));
+    ret
+}
+
+/// Filter of a tile or a list of tiles into compact notation.
+fn compact(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    let tiles = if value.is_array() {
+        Vec::<Tile>::deserialize(value)
+    } else {
+        Tile::deserialize(value).map(|t| vec![t])
+    }
+    .map_err(|e| tera::Error::chain("invalid tiles", e))?;
+    Ok(compact_tiles(&tiles).into())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::review::metrics::Metrics;
+    use crate::review::{akochan, mortal};
+    use convlog::t;
+
+    fn view(review: Review) -> View {
+        View {
+            format_version: FORMAT_VERSION,
+            game_length: GameLength::Hanchan,
+            log_id: None,
+            loading_time: Duration::ZERO,
+            review_time: Duration::ZERO,
+            metrics: vec![],
+            version: String::new(),
+            review,
+            player_id: 0,
+            tactics: None,
+            engine_log: None,
+            split_logs: None,
+            mjai_log: vec![],
+            lang: "en".to_owned(),
+            layout: Layout::Vertical,
+            offline: false,
+        }
+    }
+
+    #[test]
+    fn json_round_trip() {
+        // nothing tells them apart but the engine
+        let mortal = view(Review::Mortal(mortal::Review {
+            total_reviewed: 0,
+            total_matches: 0,
+            total_tolerated: 0,
+            tolerance: None,
+            rating: None,
+            metrics: Metrics::default(),
+            categories: vec![],
+            top_mistakes: vec![],
+            performance: Default::default(),
+            temperature: 1.,
+            kyokus: vec![],
+            relative_phi_matrix: vec![],
+            model_tag: String::new(),
+        }));
+        let akochan = view(Review::Akochan(akochan::Review {
+            total_reviewed: 0,
+            rating: None,
+            metrics: Metrics::default(),
+            categories: vec![],
+            top_mistakes: vec![],
+            defense: Default::default(),
+            kyokus: vec![],
+        }));
+
+        let json = json::to_string(&mortal).unwrap();
+        assert!(json.contains(r#""engine":"Mortal""#));
+        let read = View::from_json(json.as_bytes()).unwrap();
+        assert!(matches!(read.review, Review::Mortal(_)));
+        assert_eq!(json::to_string(&read).unwrap(), json);
+
+        let json = json::to_string(&akochan).unwrap();
+        assert!(json.contains(r#""engine":"Akochan""#));
+        let read = View::from_json(json.as_bytes()).unwrap();
+        assert!(matches!(read.review, Review::Akochan(_)));
+        assert_eq!(json::to_string(&read).unwrap(), json);
+
+        // a review of the other engine is not taken for the one claimed
+        let json = json.replace(r#""engine":"Akochan""#, r#""engine":"Mortal""#);
+        assert!(View::from_json(json.as_bytes()).is_err());
+    }
 
     #[test]
     fn template_compile() {
         let _ = &*LOCALES;
         build_base_templates().expect("failed to parse template");
     }
+
+    #[test]
+    fn compact_notation() {
+        let tiles = [
+            t!(1m),
+            t!(2m),
+            t!(5mr),
+            t!(5p),
+            t!(5pr),
+            t!(E),
+            t!(C),
+            t!(?),
+            t!(9s),
+        ];
+        assert_eq!(compact_tiles(&tiles), "120m50p17z?9s");
+    }
 }
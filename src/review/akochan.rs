@@ -1,41 +1,64 @@
+use super::category::{Breakdown, CategoryStats, Situation};
+use super::defense::{Defense, DefenseSummary, Risk};
+use super::hindsight::{self, Hindsight};
+use super::metrics::{self, Accumulator, EntryRef, Metrics, Sample, Severity, Thresholds};
+use super::safety::Safety;
+use super::session::Session;
+use crate::export::{self, Row};
 use crate::log;
-use crate::state::State;
-use convlog::{Event, Tile, tile_set_eq, tu8};
-use std::io::BufReader;
-use std::io::prelude::*;
+use crate::opts::Category;
+use crate::state::{Snapshot, State};
+use convlog::{Board, Event, Tile, tile_set_eq, tu8};
 use std::path::Path;
-use std::process::{Command, Stdio};
+use std::process::Command;
 
 use anyhow::{Context, Result, bail};
 use serde::{Deserialize, Serialize};
 use serde_json as json;
+use serde_with::skip_serializing_none;
 
-#[derive(Debug, Serialize)]
+#[skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Review {
     pub total_reviewed: usize,
-    pub rating: f64,
+    pub rating: Option<f64>,
+    #[serde(flatten)]
+    pub metrics: Metrics,
+    pub categories: Vec<CategoryStats>,
+    /// The worst decisions, worst first.
+    pub top_mistakes: Vec<EntryRef>,
+    pub defense: DefenseSummary,
     pub kyokus: Vec<KyokuReview>,
 }
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct KyokuReview {
     /// In tenhou.net/6 format, counts from 0
     pub kyoku: u8,
     pub honba: u8,
     /// Must be either (multiple) Hora(s) or one Ryukyoku
     pub end_status: Vec<Event>,
+    /// The player dealt in after a high risk discard.
+    pub risky_deal_in: bool,
 
     pub entries: Vec<Entry>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Entry {
+    /// Index into the log of the last event before the decision.
+    event_index: usize,
     junme: u8,
     tiles_left: u8,
     last_actor: u8,
     tile: Tile,
 
-    state: State,
+    category: Category,
+    /// `None` if it is the same as the engine's choice.
+    severity: Option<Severity>,
+    loss: Option<f64>,
+    state: Snapshot,
     /// Always false for akochan
     at_self_chi_pon: bool,
     /// Always false for akochan
@@ -49,9 +72,14 @@ pub struct Entry {
     details: Vec<DetailedAction>,
 
     acceptance: Acceptance,
+    /// `None` if the actual action is not a discard or its risk is unknown.
+    defense: Option<Defense>,
+    hindsight: Hindsight,
+    /// Only when facing riichi.
+    safety: Option<Safety>,
 }
 
-#[derive(Debug, Clone, Copy, Serialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 enum Acceptance {
     Disagree,
@@ -74,6 +102,21 @@ struct Detail {
     pt_exp_total: Option<f64>,
 }
 
+impl DetailedAction {
+    /// The deal-in risk of the discard made by the action, if any.
+    fn risk(&self) -> Option<Risk> {
+        let tile = self.moves.iter().find_map(|ev| match *ev {
+            Event::Dahai { pai, .. } => Some(pai),
+            _ => None,
+        })?;
+        Some(Risk {
+            tile,
+            prob: self.review.total_houjuu_hai_prob_now?,
+            value: self.review.total_houjuu_hai_value_now?,
+        })
+    }
+}
+
 pub struct Reviewer<'a> {
     pub akochan_exe: &'a Path,
     pub akochan_dir: &'a Path,
@@ -81,23 +124,60 @@ pub struct Reviewer<'a> {
     pub events: &'a [Event],
     pub player_id: u8,
     pub deviation_threshold: f64,
+    /// Review only these categories if not empty.
+    pub only: &'a [Category],
+    pub thresholds: Thresholds,
+    /// Number of the worst decisions to rank.
+    pub top_mistakes: usize,
     pub verbose: bool,
 }
 
+impl Review {
+    /// One row for each entry.
+    pub fn rows(&self, game_id: Option<&str>, seat: u8) -> Vec<Row> {
+        self.kyokus
+            .iter()
+            .flat_map(|k| {
+                k.entries.iter().map(move |e| Row {
+                    game_id: game_id.map(ToOwned::to_owned),
+                    seat,
+                    kyoku: export::kyoku_name(k.kyoku),
+                    honba: k.honba,
+                    junme: e.junme,
+                    tiles_left: e.tiles_left,
+                    category: e.category,
+                    severity: e.severity,
+                    shanten: None,
+                    at_furiten: None,
+                    actual: export::describe(&e.actual),
+                    expected: export::describe(&e.expected),
+                    is_equal: matches!(e.acceptance, Acceptance::Agree),
+                    is_tolerable: matches!(e.acceptance, Acceptance::Tolerable),
+                    actual_value: e
+                        .details
+                        .iter()
+                        .find(|d| compare_action_strict(&e.actual, &d.moves))
+                        .and_then(|d| d.review.pt_exp_total),
+                    expected_value: e.details.first().and_then(|d| d.review.pt_exp_total),
+                    loss: e.loss,
+                })
+            })
+            .collect()
+    }
+}
+
 impl Reviewer<'_> {
-    pub fn review(&self) -> Result<Review> {
+    /// The command to spawn the engine with.
+    pub fn command(&self) -> Command {
         let &Self {
             akochan_exe,
             akochan_dir,
             tactics_config,
-            events,
             player_id,
-            deviation_threshold,
             verbose,
+            ..
         } = self;
 
-        let mut kyoku_reviews = vec![];
-
         let player_id_string = player_id.to_string();
         let args = &[
             "pipe_detailed".as_ref(),
@@ -114,47 +194,55 @@ impl Reviewer<'_> {
             );
         }
 
-        let mut akochan = Command::new(akochan_exe)
-            .args(args)
-            .current_dir(Path::new(akochan_dir))
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::inherit())
-            .spawn()
-            .context("failed to spawn engine")?;
-
-        let mut stdin = akochan
-            .stdin
-            .take()
-            .context("failed to get stdin of engine")?;
-        let stdout = akochan
-            .stdout
-            .take()
-            .context("failed to get stdout of engine")?;
-        let mut stdout_lines = BufReader::new(stdout).lines();
+        let mut command = Command::new(akochan_exe);
+        command.args(args).current_dir(akochan_dir);
+        command
+    }
+
+    pub fn review(&self, akochan: &mut Session) -> Result<Review> {
+        let &Self {
+            events,
+            player_id,
+            deviation_threshold,
+            only,
+            thresholds,
+            top_mistakes,
+            verbose,
+            ..
+        } = self;
+
+        let mut kyoku_reviews = vec![];
 
         let events_len = events.len();
         let mut total_reviewed = 0;
         let mut total_tolerated = 0;
         let mut total_problems = 0;
         let mut raw_rating = 0.;
+        let mut metrics = Accumulator::new(thresholds);
+        let mut breakdown = Breakdown::default();
+        let mut defense = DefenseSummary::default();
 
         let mut kyoku_review = KyokuReview::default();
         let mut state = State::new(player_id);
+        let mut table = Board::default();
         let mut junme = 0;
         let mut tiles_left = 70;
         let mut entries = vec![];
         let mut is_riichied = false;
+        let mut facing_riichi = false;
 
         for (i, event) in events.iter().enumerate() {
             let to_write = json::to_string(event).unwrap();
-            writeln!(stdin, "{to_write}").context("failed to write to engine")?;
-            if verbose {
-                log!("> {to_write}");
-            }
+            akochan.send(&to_write).with_context(|| {
+                format!(
+                    "at kyoku {}, honba {}, event #{i}",
+                    kyoku_review.kyoku, kyoku_review.honba,
+                )
+            })?;
 
             // update the state
             state.update(event).context("failed to update state")?;
+            table.apply(event).context("failed to replay the table")?;
 
             // this match does two things:
             // 1. setting board metadata like bakaze, kyoku, honba, junme
@@ -170,12 +258,20 @@ impl Reviewer<'_> {
                     kyoku_review.kyoku = kyoku;
                     kyoku_review.honba = honba;
                     is_riichied = false;
+                    facing_riichi = false;
                     tiles_left = 70;
 
                     continue;
                 }
 
                 Event::EndKyoku => {
+                    let dealt_in = kyoku_review.end_status.iter().any(|s| {
+                        matches!(*s, Event::Hora { actor, target, .. } if actor != target && target == player_id)
+                    });
+                    let pushed = entries
+                        .iter()
+                        .any(|e: &Entry| e.defense.is_some_and(|d| d.is_high_risk));
+                    kyoku_review.risky_deal_in = defense.push_kyoku(dealt_in, pushed);
                     kyoku_review.entries.clone_from(&entries);
                     entries.clear();
 
@@ -215,6 +311,8 @@ impl Reviewer<'_> {
                 Event::ReachAccepted { actor } => {
                     if actor == player_id {
                         is_riichied = true;
+                    } else {
+                        facing_riichi = true;
                     }
                     continue;
                 }
@@ -235,14 +333,12 @@ impl Reviewer<'_> {
                 bail!("wrong size of input events, expected to have 4 more");
             }
 
-            // be careful, stdout_lines.next() may block.
-            let line = stdout_lines
-                .next()
-                .context("failed to read from engine: unexpected EOF")?
-                .context("failed to read from engine")?;
-            if verbose {
-                log!("< {}", line.trim());
-            }
+            let line = akochan.recv().with_context(|| {
+                format!(
+                    "failed to read from engine at kyoku {}, honba {}, event #{i}",
+                    kyoku_review.kyoku, kyoku_review.honba,
+                )
+            })?;
 
             let actions: Vec<DetailedAction> =
                 json::from_str(&line).context("failed to parse JSON output of engine")?;
@@ -270,6 +366,30 @@ impl Reviewer<'_> {
                 }
             }
 
+            let mut situation = Situation {
+                own_turn: matches!(event, Event::Tsumo { .. }),
+                facing_riichi,
+                ..Situation::default()
+            };
+            // akochan may give no option to call when the player did
+            let options = actions.iter().map(|a| &a.moves[0]);
+            for ev in options.chain(&actual_action[..1]) {
+                match *ev {
+                    Event::Hora { .. } => situation.can_agari = true,
+                    Event::Ryukyoku { .. } => situation.can_ryukyoku = true,
+                    Event::Chi { .. } | Event::Pon { .. } => situation.can_chi_pon = true,
+                    Event::Daiminkan { .. } | Event::Ankan { .. } | Event::Kakan { .. } => {
+                        situation.can_kan = true;
+                    }
+                    Event::Reach { .. } => situation.can_riichi = true,
+                    _ => (),
+                }
+            }
+            let category = situation.category();
+            if !only.is_empty() && !only.contains(&category) {
+                continue;
+            }
+
             let expected_action = &actions[0].moves; // best move
             let is_equal_or_innocent = compare_action(actual_action, expected_action, player_id)
                 .context("invalid state in event")?;
@@ -365,12 +485,53 @@ impl Reviewer<'_> {
             total_reviewed += 1;
             raw_rating += move_rating;
 
+            let rank = actions
+                .iter()
+                .position(|a| compare_action_strict(&actual_action_strict, &a.moves));
+            let pt_exp_total = |i: usize| actions[i].review.pt_exp_total;
+            let sample = Sample {
+                is_equal: is_equal_or_innocent,
+                is_tolerable: matches!(acceptance, Acceptance::Tolerable),
+                rank,
+                loss: rank.and_then(|i| Some(pt_exp_total(0)? - pt_exp_total(i)?)),
+                prob: None,
+            };
+            metrics.push(&sample);
+            breakdown.push(category, &sample);
+            let loss = if sample.is_equal {
+                Some(0.)
+            } else {
+                sample.loss
+            };
+            let severity = thresholds.severity(sample.is_equal || sample.is_tolerable, loss);
+            let entry_defense = rank
+                .and_then(|i| actions[i].risk())
+                .map(|actual| Defense::new(actual, actions.iter().filter_map(|a| a.risk())));
+            if let Some(d) = &entry_defense {
+                defense.push(d);
+            }
+
+            let discard = actual_action_strict.iter().find_map(|ev| match *ev {
+                Event::Dahai { actor, pai, .. } if actor == player_id => Some(pai),
+                _ => None,
+            });
+            let hindsight = hindsight::hindsight(&table, player_id, discard, &events[i + 1..]);
+            let expected_discard = expected_action.iter().find_map(|ev| match *ev {
+                Event::Dahai { actor, pai, .. } if actor == player_id => Some(pai),
+                _ => None,
+            });
+            let safety = Safety::new(&state, discard, expected_discard);
+
             let entry = Entry {
+                event_index: i,
                 junme,
                 tiles_left,
                 last_actor,
                 tile,
-                state: state.clone(),
+                category,
+                severity,
+                loss,
+                state: state.snapshot(),
                 at_self_chi_pon: false,
                 at_self_riichi: false,
                 at_opponent_kakan,
@@ -378,6 +539,9 @@ impl Reviewer<'_> {
                 actual: actual_action_strict,
                 details: actions,
                 acceptance,
+                defense: entry_defense,
+                hindsight,
+                safety,
             };
             log!(
                 "review entry created: {acceptance:?}
@@ -390,19 +554,24 @@ impl Reviewer<'_> {
 
             entries.push(entry);
         }
-        drop(stdin);
-
-        let status = akochan.wait()?;
-        if !status.success() {
-            if let Some(code) = status.code() {
-                bail!("non-zero exit code: {code}");
-            }
-            bail!("process terminated by signal");
-        }
+        akochan.close_stdin();
 
         Ok(Review {
             total_reviewed,
-            rating: (raw_rating / total_reviewed as f64).powi(2),
+            rating: (total_reviewed > 0).then(|| (raw_rating / total_reviewed as f64).powi(2)),
+            metrics: metrics.finish(),
+            categories: breakdown.finish(),
+            defense,
+            top_mistakes: metrics::top_mistakes(
+                kyoku_reviews.iter().enumerate().flat_map(|(k, kyoku)| {
+                    kyoku
+                        .entries
+                        .iter()
+                        .enumerate()
+                        .map(move |(e, entry)| (k, e, entry.severity, entry.loss))
+                }),
+                top_mistakes,
+            ),
             kyokus: kyoku_reviews,
         })
     }
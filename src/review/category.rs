@@ -0,0 +1,99 @@
+use super::metrics::Sample;
+use crate::opts::Category;
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// What could be done at a decision, from which its category is told.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Situation {
+    /// After a tsumo or a call of the player, as opposed to after a discard
+    /// or kakan of an opponent.
+    pub own_turn: bool,
+    pub can_agari: bool,
+    pub can_ryukyoku: bool,
+    pub can_chi_pon: bool,
+    pub can_kan: bool,
+    pub can_riichi: bool,
+    /// Choosing the tile to discard for a riichi just declared.
+    pub at_self_riichi: bool,
+    /// Any opponent is in riichi.
+    pub facing_riichi: bool,
+}
+
+impl Situation {
+    /// When there are several kinds of choices, the rarer one decides.
+    pub const fn category(self) -> Category {
+        if self.can_agari {
+            Category::Agari
+        } else if self.can_ryukyoku {
+            Category::Kyushukyuhai
+        } else if !self.own_turn && self.can_chi_pon {
+            Category::Call
+        } else if self.can_kan {
+            Category::Kan
+        } else if self.can_riichi || self.at_self_riichi {
+            Category::Riichi
+        } else if self.facing_riichi {
+            Category::Defense
+        } else {
+            Category::Efficiency
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryStats {
+    pub category: Category,
+    pub total: usize,
+    pub matches: usize,
+    pub tolerated: usize,
+    /// Same as `total_loss` of `Metrics`, but within the category.
+    pub total_loss: f64,
+    pub mean_loss: f64,
+}
+
+/// Accumulates samples into stats per category.
+#[derive(Default)]
+pub struct Breakdown {
+    // (total, matches, tolerated, decisions with loss, total loss)
+    stats: BTreeMap<Category, (usize, usize, usize, usize, f64)>,
+}
+
+impl Breakdown {
+    pub fn push(&mut self, category: Category, sample: &Sample) {
+        let (total, matches, tolerated, with_loss, total_loss) =
+            self.stats.entry(category).or_default();
+        *total += 1;
+        if sample.is_tolerable {
+            *tolerated += 1;
+        }
+        if sample.is_equal {
+            *matches += 1;
+            *with_loss += 1;
+        } else if let Some(loss) = sample.loss {
+            *with_loss += 1;
+            *total_loss += loss.max(0.);
+        }
+    }
+
+    pub fn finish(self) -> Vec<CategoryStats> {
+        self.stats
+            .into_iter()
+            .map(
+                |(category, (total, matches, tolerated, with_loss, total_loss))| CategoryStats {
+                    category,
+                    total,
+                    matches,
+                    tolerated,
+                    total_loss,
+                    mean_loss: if with_loss == 0 {
+                        0.
+                    } else {
+                        total_loss / with_loss as f64
+                    },
+                },
+            )
+            .collect()
+    }
+}
@@ -0,0 +1,113 @@
+use convlog::Tile;
+
+use serde::{Deserialize, Serialize};
+
+/// A deal-in probability from which a discard is a push.
+pub const HIGH_RISK: f64 = 0.1;
+
+/// Deal-in risk of a discard, as estimated by akochan.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Risk {
+    pub tile: Tile,
+    /// Probability of dealing in.
+    pub prob: f64,
+    /// Points expected to be lost by dealing in.
+    pub value: f64,
+}
+
+/// The risk of the actual discard against the safest one available.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Defense {
+    pub actual: Risk,
+    pub safest: Risk,
+    /// The actual discard has a deal-in probability of at least `HIGH_RISK`.
+    pub is_high_risk: bool,
+}
+
+impl Defense {
+    /// `candidates` are the risks of all the discards available, which
+    /// should include `actual`.
+    pub fn new(actual: Risk, candidates: impl IntoIterator<Item = Risk>) -> Self {
+        let safest = candidates
+            .into_iter()
+            .fold(actual, |min, r| if r.prob < min.prob { r } else { min });
+        Self {
+            actual,
+            safest,
+            is_high_risk: actual.prob >= HIGH_RISK,
+        }
+    }
+}
+
+/// Risks taken over the game, against the least that had to be taken.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DefenseSummary {
+    /// Number of discards whose risks are known.
+    pub decisions: usize,
+    /// Sum of the deal-in probabilities of the actual discards.
+    pub total_risk_taken: f64,
+    /// Sum of the deal-in probabilities of the safest discards.
+    pub total_risk_necessary: f64,
+    pub total_value_taken: f64,
+    pub total_value_necessary: f64,
+    /// Discards with a deal-in probability of at least `HIGH_RISK`.
+    pub high_risk_decisions: usize,
+    pub deal_ins: usize,
+    /// Deal-ins in kyokus where the player has made a high risk discard.
+    pub risky_deal_ins: usize,
+}
+
+impl DefenseSummary {
+    pub fn push(&mut self, defense: &Defense) {
+        self.decisions += 1;
+        self.total_risk_taken += defense.actual.prob;
+        self.total_risk_necessary += defense.safest.prob;
+        self.total_value_taken += defense.actual.value;
+        self.total_value_necessary += defense.safest.value;
+        if defense.is_high_risk {
+            self.high_risk_decisions += 1;
+        }
+    }
+
+    /// Counts a kyoku and returns whether the player pushed and dealt in.
+    pub const fn push_kyoku(&mut self, dealt_in: bool, pushed: bool) -> bool {
+        if dealt_in {
+            self.deal_ins += 1;
+            if pushed {
+                self.risky_deal_ins += 1;
+            }
+        }
+        dealt_in && pushed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use convlog::t;
+
+    #[test]
+    fn safest() {
+        let risk = |tile, prob| Risk {
+            tile,
+            prob,
+            value: prob * 8000.,
+        };
+        let actual = risk(t!(5m), 0.2);
+        let candidates = [risk(t!(E), 0.), risk(t!(5m), 0.2), risk(t!(1p), 0.05)];
+        let defense = Defense::new(actual, candidates);
+        assert_eq!(defense.safest.tile, t!(E));
+        assert!(defense.is_high_risk);
+
+        let mut summary = DefenseSummary::default();
+        summary.push(&defense);
+        summary.push(&Defense::new(risk(t!(E), 0.), []));
+        assert_eq!(summary.decisions, 2);
+        assert_eq!(summary.high_risk_decisions, 1);
+        assert!((summary.total_risk_taken - 0.2).abs() < 1e-9);
+        assert!(summary.total_risk_necessary.abs() < 1e-9);
+        assert!(!summary.push_kyoku(true, false));
+        assert!(summary.push_kyoku(true, true));
+        assert_eq!((summary.deal_ins, summary.risky_deal_ins), (2, 1));
+    }
+}
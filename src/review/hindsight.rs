@@ -0,0 +1,131 @@
+use convlog::shanten;
+use convlog::{Board, Event, Tile, must_tile};
+
+use serde::{Deserialize, Serialize};
+
+/// Number of the upcoming draws shown in hindsight.
+pub const NEXT_DRAWS: usize = 3;
+
+/// What can only be known by looking at every hand and the rest of the log,
+/// which is never given to the engine.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Hindsight {
+    /// Opponents whose hands are fully known, in absolute seat order.
+    pub opponents: Vec<OpponentHand>,
+    /// Opponents whose waits include the tile actually discarded at the
+    /// decision, or right after declaring riichi, regardless of yaku and
+    /// furiten.
+    pub deal_in_to: Vec<u8>,
+    /// The tiles the player actually drew next in the kyoku, which can differ
+    /// from what would have been drawn after another action.
+    pub next_draws: Vec<Tile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpponentHand {
+    pub seat: u8,
+    pub shanten: i8,
+    /// Only for tenpai hands waiting for a discard.
+    pub waits: Vec<Tile>,
+}
+
+/// `table` is the board replayed from the unmasked log, whose hands
+/// containing unknown tiles, as in logs not from the player's perspective,
+/// are left out. `discard` is the tile the player actually discarded at the
+/// decision, if any, and `rest` are the events after the decision.
+pub fn hindsight(table: &Board, player_id: u8, discard: Option<Tile>, rest: &[Event]) -> Hindsight {
+    let opponents: Vec<_> = (0..4)
+        .filter(|&seat| seat != player_id && table.seats[seat as usize].is_hand_known())
+        .map(|seat| {
+            let hand = table.seats[seat as usize].counts();
+            let len_div3 = hand.iter().sum::<u8>() / 3;
+            let shanten = shanten::calc_all(&hand, len_div3);
+            OpponentHand {
+                seat,
+                shanten,
+                waits: waits(&hand, len_div3),
+            }
+        })
+        .collect();
+    let deal_in_to = discard
+        .map(|d| {
+            opponents
+                .iter()
+                .filter(|o| o.waits.contains(&d.deaka()))
+                .map(|o| o.seat)
+                .collect()
+        })
+        .unwrap_or_default();
+    let next_draws = rest
+        .iter()
+        .take_while(|ev| !matches!(ev, Event::EndKyoku))
+        .filter_map(|ev| match *ev {
+            Event::Tsumo { actor, pai } if actor == player_id => Some(pai),
+            _ => None,
+        })
+        .take(NEXT_DRAWS)
+        .collect();
+    Hindsight {
+        opponents,
+        deal_in_to,
+        next_draws,
+    }
+}
+
+/// The tiles completing a hand of `len_div3 * 3 + 1` tiles, except the ones
+/// all held by itself.
+fn waits(hand: &[u8; 34], len_div3: u8) -> Vec<Tile> {
+    if hand.iter().sum::<u8>() % 3 != 1 {
+        return vec![];
+    }
+    let mut hand = *hand;
+    (0..34)
+        .filter(|&id| {
+            if hand[id] >= 4 {
+                return false;
+            }
+            hand[id] += 1;
+            let agari = shanten::calc_all(&hand, len_div3) == -1;
+            hand[id] -= 1;
+            agari
+        })
+        .map(|id| must_tile!(id))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use convlog::t;
+    use serde_json as json;
+
+    #[test]
+    fn waits_and_deal_in() {
+        let start = r#"{"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","S","W"],["2m","3m","4m","5mr","6m","7m","2p","3p","4p","6s","7s","N","N"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["1m","9m","1p","9p","1s","9s","E","S","W","N","P","F","C"]]}"#;
+        let mut table = Board::default();
+        table.apply(&json::from_str(start).unwrap()).unwrap();
+        let rest: Vec<Event> = [
+            r#"{"type":"tsumo","actor":0,"pai":"N"}"#,
+            r#"{"type":"dahai","actor":0,"pai":"N","tsumogiri":true}"#,
+            r#"{"type":"tsumo","actor":0,"pai":"C"}"#,
+            r#"{"type":"end_kyoku"}"#,
+            r#"{"type":"tsumo","actor":0,"pai":"P"}"#,
+        ]
+        .iter()
+        .map(|s| json::from_str(s).unwrap())
+        .collect();
+
+        let h = hindsight(&table, 0, Some(t!(8s)), &rest);
+        // the hand of seat 2 is unknown
+        let seats: Vec<_> = h.opponents.iter().map(|o| o.seat).collect();
+        assert_eq!(seats, [1, 3]);
+        assert_eq!(h.opponents[0].shanten, 0);
+        assert_eq!(h.opponents[0].waits, [t!(5s), t!(8s)]);
+        assert_eq!(h.opponents[1].waits.len(), 13);
+        assert_eq!(h.deal_in_to, [1]);
+        assert_eq!(h.next_draws, [t!(N), t!(C)]);
+
+        let h = hindsight(&table, 0, Some(t!(2s)), &rest);
+        assert!(h.deal_in_to.is_empty());
+    }
+}
@@ -0,0 +1,296 @@
+use crate::opts::Metric;
+
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+
+/// Number of the top actions considered by top-k agreement, counting from 1.
+pub const TOP_K: usize = 3;
+
+/// How bad a decision different from the engine's is, by its loss.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Minor,
+    Mistake,
+    Blunder,
+}
+
+impl Severity {
+    /// How much it weighs in `weighted_mistakes`.
+    const fn weight(self) -> f64 {
+        match self {
+            Self::Minor => 1.,
+            Self::Mistake => 2.,
+            Self::Blunder => 4.,
+        }
+    }
+}
+
+/// Losses from which a decision becomes a mistake or a blunder.
+#[derive(Debug, Clone, Copy)]
+pub struct Thresholds {
+    pub mistake: f64,
+    pub blunder: f64,
+}
+
+impl Thresholds {
+    /// Gaps of Q-values.
+    pub const MORTAL: Self = Self {
+        mistake: 0.05,
+        blunder: 0.2,
+    };
+    /// Gaps of `pt_exp_total`.
+    pub const AKOCHAN: Self = Self {
+        mistake: 1.,
+        blunder: 5.,
+    };
+
+    /// Returns `None` if it is the same as the engine's choice. A different
+    /// decision whose loss is unknown is minor.
+    pub fn severity(self, is_equal: bool, loss: Option<f64>) -> Option<Severity> {
+        if is_equal {
+            return None;
+        }
+        let severity = match loss {
+            Some(l) if l >= self.blunder => Severity::Blunder,
+            Some(l) if l >= self.mistake => Severity::Mistake,
+            _ => Severity::Minor,
+        };
+        Some(severity)
+    }
+}
+
+/// Points to an entry in the review.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntryRef {
+    /// Index into `kyokus` of the review.
+    pub kyoku_index: usize,
+    /// Index into `entries` of the kyoku.
+    pub entry_index: usize,
+    pub severity: Severity,
+    pub loss: Option<f64>,
+}
+
+/// Ranks the `n` worst decisions out of the entries given as `(kyoku index,
+/// entry index, severity, loss)`, in which the ones with unknown losses come
+/// last within the same severity.
+pub fn top_mistakes(
+    entries: impl Iterator<Item = (usize, usize, Option<Severity>, Option<f64>)>,
+    n: usize,
+) -> Vec<EntryRef> {
+    let mut mistakes: Vec<_> = entries
+        .filter_map(|(kyoku_index, entry_index, severity, loss)| {
+            Some(EntryRef {
+                kyoku_index,
+                entry_index,
+                severity: severity?,
+                loss,
+            })
+        })
+        .collect();
+    // stable, so that earlier ones come first in a tie
+    mistakes.sort_by(|l, r| {
+        r.severity
+            .cmp(&l.severity)
+            .then_with(|| match (l.loss, r.loss) {
+                (Some(l), Some(r)) => r.total_cmp(&l),
+                (l, r) => r.is_some().cmp(&l.is_some()),
+            })
+    });
+    mistakes.truncate(n);
+    mistakes
+}
+
+/// Metrics of a review other than the match rate. They are all optional so
+/// that the ones not chosen by the user are left out.
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Metrics {
+    /// Sum of the gaps between the value of the best action and the actual
+    /// one, which are Q-values for Mortal and `pt_exp_total` for akochan.
+    pub total_loss: Option<f64>,
+    /// `total_loss` over the number of decisions whose loss is known.
+    pub mean_loss: Option<f64>,
+    /// Mean probability given to the actual action by the policy. Mortal only.
+    pub mean_actual_prob: Option<f64>,
+    /// The ratio of decisions in which the actual action is among the top 1,
+    /// 2, ..., `TOP_K` actions.
+    pub top_k_agreement: Option<[f64; TOP_K]>,
+    /// Number of mistakes, in which the ones with larger losses weigh more.
+    pub weighted_mistakes: Option<f64>,
+}
+
+impl Metrics {
+    /// Leaves out the metrics not in `selected`.
+    pub fn retain(&mut self, selected: &[Metric]) {
+        if !selected.contains(&Metric::Loss) {
+            self.total_loss = None;
+            self.mean_loss = None;
+        }
+        if !selected.contains(&Metric::Prob) {
+            self.mean_actual_prob = None;
+        }
+        if !selected.contains(&Metric::TopK) {
+            self.top_k_agreement = None;
+        }
+        if !selected.contains(&Metric::Mistakes) {
+            self.weighted_mistakes = None;
+        }
+    }
+}
+
+/// One reviewed decision.
+pub struct Sample {
+    pub is_equal: bool,
+    /// Different from the engine's choice, but close enough not to count as a
+    /// mistake.
+    pub is_tolerable: bool,
+    /// Position of the actual action in the engine's list of candidates,
+    /// counting from 0.
+    pub rank: Option<usize>,
+    pub loss: Option<f64>,
+    pub prob: Option<f64>,
+}
+
+/// Accumulates samples into `Metrics`.
+pub struct Accumulator {
+    thresholds: Thresholds,
+    total: usize,
+    with_loss: usize,
+    with_prob: usize,
+    total_loss: f64,
+    total_prob: f64,
+    top_k: [usize; TOP_K],
+    weighted_mistakes: f64,
+}
+
+impl Accumulator {
+    pub const fn new(thresholds: Thresholds) -> Self {
+        Self {
+            thresholds,
+            total: 0,
+            with_loss: 0,
+            with_prob: 0,
+            total_loss: 0.,
+            total_prob: 0.,
+            top_k: [0; TOP_K],
+            weighted_mistakes: 0.,
+        }
+    }
+
+    pub fn push(&mut self, sample: &Sample) {
+        self.total += 1;
+        let loss = if sample.is_equal {
+            Some(0.)
+        } else {
+            sample.loss.map(|l| l.max(0.))
+        };
+        if let Some(loss) = loss {
+            self.with_loss += 1;
+            self.total_loss += loss;
+        }
+        if let Some(prob) = sample.prob {
+            self.with_prob += 1;
+            self.total_prob += prob;
+        }
+
+        let rank = if sample.is_equal {
+            Some(0)
+        } else {
+            sample.rank
+        };
+        if let Some(rank) = rank {
+            self.top_k
+                .iter_mut()
+                .skip(rank)
+                .for_each(|count| *count += 1);
+        }
+        if !sample.is_tolerable
+            && let Some(severity) = self.thresholds.severity(sample.is_equal, loss)
+        {
+            self.weighted_mistakes += severity.weight();
+        }
+    }
+
+    pub fn finish(self) -> Metrics {
+        let ratio = |x: f64, n: usize| if n == 0 { 0. } else { x / n as f64 };
+        Metrics {
+            total_loss: Some(self.total_loss),
+            mean_loss: (self.with_loss > 0).then(|| ratio(self.total_loss, self.with_loss)),
+            mean_actual_prob: (self.with_prob > 0).then(|| ratio(self.total_prob, self.with_prob)),
+            top_k_agreement: (self.total > 0)
+                .then(|| self.top_k.map(|n| ratio(n as f64, self.total))),
+            weighted_mistakes: Some(self.weighted_mistakes),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn accumulate() {
+        let mut acc = Accumulator::new(Thresholds::MORTAL);
+        let samples = [
+            (true, false, Some(0), Some(0.), Some(0.9)),
+            (false, false, Some(1), Some(0.01), Some(0.3)),
+            (false, false, Some(2), Some(0.1), Some(0.2)),
+            (false, false, Some(5), Some(1.), Some(0.)),
+            (false, true, Some(1), Some(0.06), Some(0.4)),
+        ];
+        for (is_equal, is_tolerable, rank, loss, prob) in samples {
+            acc.push(&Sample {
+                is_equal,
+                is_tolerable,
+                rank,
+                loss,
+                prob,
+            });
+        }
+        let metrics = acc.finish();
+        assert!((metrics.total_loss.unwrap() - 1.17).abs() < 1e-9);
+        assert!((metrics.mean_loss.unwrap() - 1.17 / 5.).abs() < 1e-9);
+        assert!((metrics.mean_actual_prob.unwrap() - 0.36).abs() < 1e-9);
+        assert_eq!(metrics.top_k_agreement, Some([0.2, 0.6, 0.8]));
+        assert_eq!(metrics.weighted_mistakes, Some(1. + 2. + 4.));
+
+        let mut metrics = metrics;
+        metrics.retain(&[Metric::TopK]);
+        assert!(metrics.total_loss.is_none() && metrics.weighted_mistakes.is_none());
+        assert!(metrics.top_k_agreement.is_some());
+
+        // nothing reviewed
+        let metrics = Accumulator::new(Thresholds::AKOCHAN).finish();
+        assert_eq!(metrics.total_loss, Some(0.));
+        assert_eq!(metrics.mean_loss, None);
+        assert_eq!(metrics.mean_actual_prob, None);
+        assert_eq!(metrics.top_k_agreement, None);
+    }
+
+    #[test]
+    fn rank_mistakes() {
+        let t = Thresholds::MORTAL;
+        let entries = [
+            (0, 0, t.severity(false, Some(0.01)), Some(0.01)),
+            (0, 1, t.severity(true, Some(0.)), Some(0.)),
+            (1, 0, t.severity(false, None), None),
+            (1, 1, t.severity(false, Some(0.3)), Some(0.3)),
+            (1, 2, t.severity(false, Some(0.1)), Some(0.1)),
+            (2, 0, t.severity(false, Some(0.5)), Some(0.5)),
+        ];
+        let top: Vec<_> = top_mistakes(entries.into_iter(), 4)
+            .into_iter()
+            .map(|e| (e.kyoku_index, e.entry_index, e.severity))
+            .collect();
+        assert_eq!(
+            top,
+            [
+                (2, 0, Severity::Blunder),
+                (1, 1, Severity::Blunder),
+                (1, 2, Severity::Mistake),
+                (0, 0, Severity::Minor),
+            ],
+        );
+    }
+}
@@ -1,11 +1,59 @@
 pub mod akochan;
+pub mod category;
+pub mod defense;
+pub mod hindsight;
+pub mod metrics;
 pub mod mortal;
+pub mod process;
+pub mod safety;
+pub mod session;
+pub mod transcript;
 
-use serde::Serialize;
+use crate::export::Row;
+use crate::opts::Metric;
 
-#[derive(Serialize)]
-#[serde(untagged)]
+use serde::{Deserialize, Serialize};
+
+/// Flattened into its container as the `engine` and `review` fields, so that
+/// the engine tells which kind of review to read back.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "engine", content = "review")]
 pub enum Review {
     Akochan(akochan::Review),
     Mortal(mortal::Review),
 }
+
+impl Review {
+    /// Leaves out the metrics not in `selected`.
+    pub fn retain_metrics(&mut self, selected: &[Metric]) {
+        let (rating, metrics) = match self {
+            Self::Akochan(r) => (&mut r.rating, &mut r.metrics),
+            Self::Mortal(r) => (&mut r.rating, &mut r.metrics),
+        };
+        if !selected.contains(&Metric::Rating) {
+            *rating = None;
+        }
+        metrics.retain(selected);
+    }
+
+    pub const fn total_reviewed(&self) -> usize {
+        match self {
+            Self::Akochan(r) => r.total_reviewed,
+            Self::Mortal(r) => r.total_reviewed,
+        }
+    }
+
+    pub const fn rating(&self) -> Option<f64> {
+        match self {
+            Self::Akochan(r) => r.rating,
+            Self::Mortal(r) => r.rating,
+        }
+    }
+
+    pub fn rows(&self, game_id: Option<&str>, seat: u8) -> Vec<Row> {
+        match self {
+            Self::Akochan(r) => r.rows(game_id, seat),
+            Self::Mortal(r) => r.rows(game_id, seat),
+        }
+    }
+}
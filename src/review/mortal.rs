@@ -1,23 +1,38 @@
+use super::category::{Breakdown, CategoryStats, Situation};
+use super::hindsight::{self, Hindsight};
+use super::metrics::{self, Accumulator, EntryRef, Metrics, Sample, Severity, Thresholds};
+use super::safety::Safety;
+use super::session::Session;
+use crate::export::{self, Row};
 use crate::log;
+use crate::opts::Category;
 use crate::softmax::softmax;
-use crate::state::State;
-use convlog::{Event, Tile, must_tile, t, tile_set_eq, tu8};
-use std::io::BufReader;
-use std::io::prelude::*;
+use crate::state::{Snapshot, State};
+use convlog::{Board, Event, Tile, must_tile, tu8};
 use std::path::Path;
-use std::process::{Command, Stdio};
-use std::{array, mem};
+use std::process::Command;
+use std::{array, mem, slice};
 
 use anyhow::{Context, Result, bail, ensure};
 use serde::{Deserialize, Serialize};
 use serde_json as json;
 use serde_with::skip_serializing_none;
 
-#[derive(Debug, Serialize)]
+#[skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Review {
     pub total_reviewed: usize,
     pub total_matches: usize,
-    pub rating: f64,
+    /// Decisions different from Mortal's but within the tolerance.
+    pub total_tolerated: usize,
+    pub tolerance: Option<Tolerance>,
+    pub rating: Option<f64>,
+    #[serde(flatten)]
+    pub metrics: Metrics,
+    pub categories: Vec<CategoryStats>,
+    /// The worst decisions, worst first.
+    pub top_mistakes: Vec<EntryRef>,
+    pub performance: Performance,
     pub temperature: f32,
     pub kyokus: Vec<KyokuReview>,
 
@@ -25,7 +40,7 @@ pub struct Review {
     pub model_tag: String,
 }
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct KyokuReview {
     /// In tenhou.net/6 format, counts from 0
     pub kyoku: u8,
@@ -38,14 +53,20 @@ pub struct KyokuReview {
 }
 
 #[skip_serializing_none]
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Entry {
+    /// Index into the log of the last event before the decision.
+    event_index: usize,
     junme: u8,
     tiles_left: u8,
     last_actor: u8,
     tile: Tile,
 
-    state: State,
+    category: Category,
+    /// `None` if it is the same as the engine's choice.
+    severity: Option<Severity>,
+    loss: Option<f64>,
+    state: Snapshot,
     at_self_chi_pon: bool,
     at_self_riichi: bool,
     at_opponent_kakan: bool,
@@ -53,18 +74,28 @@ pub struct Entry {
     expected: Event,
     actual: Event,
     is_equal: bool,
+    is_tolerable: bool,
     details: Vec<Detail>,
 
     shanten: i8,
     at_furiten: bool,
     actual_index: usize,
+
+    is_greedy: Option<bool>,
+    batch_size: Option<usize>,
+    /// Including the time of kan select, if any.
+    eval_time_ns: Option<u64>,
+
+    hindsight: Hindsight,
+    /// Only when facing riichi.
+    safety: Option<Safety>,
 }
 
-#[derive(Debug, Clone, Serialize)]
-struct Detail {
-    action: Event,
-    q_value: f32,
-    prob: f32,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Detail {
+    pub action: Event,
+    pub q_value: f32,
+    pub prob: f32,
 
     // not displayed, for sorting only
     #[serde(skip)]
@@ -77,6 +108,13 @@ enum Label {
     KanSelect(usize),
 }
 
+/// Only for details read back from JSON, which are never sorted again.
+impl Default for Label {
+    fn default() -> Self {
+        Self::General(0)
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 struct RawAction {
     #[serde(flatten)]
@@ -95,7 +133,56 @@ struct Metadata {
     eval_time_ns: Option<u64>,
     shanten: Option<i8>,
     at_furiten: Option<bool>,
-    kan_select: Option<Box<Metadata>>,
+    kan_select: Option<Box<Self>>,
+}
+
+/// How Mortal ran over the game, from the metadata of all its responses,
+/// including the ones for decisions not reviewed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Performance {
+    /// Number of responses with an inference time.
+    pub timed: usize,
+    pub total_eval_time_ns: u64,
+    pub p50_eval_time_ns: u64,
+    pub p90_eval_time_ns: u64,
+    pub p99_eval_time_ns: u64,
+    pub max_eval_time_ns: u64,
+    /// Decisions sampled rather than taken greedily, which means the config
+    /// is not fit for reviews.
+    pub non_greedy: usize,
+    pub max_batch_size: Option<usize>,
+}
+
+impl Performance {
+    fn new(mut eval_times: Vec<u64>, non_greedy: usize, max_batch_size: Option<usize>) -> Self {
+        eval_times.sort_unstable();
+        // nearest rank
+        let percentile = |p: usize| {
+            let rank = (eval_times.len() * p).div_ceil(100).max(1);
+            eval_times.get(rank - 1).copied().unwrap_or_default()
+        };
+        Self {
+            timed: eval_times.len(),
+            total_eval_time_ns: eval_times.iter().sum(),
+            p50_eval_time_ns: percentile(50),
+            p90_eval_time_ns: percentile(90),
+            p99_eval_time_ns: percentile(99),
+            max_eval_time_ns: eval_times.last().copied().unwrap_or_default(),
+            non_greedy,
+            max_batch_size,
+        }
+    }
+}
+
+/// Mortal's view on the decision at the end of a sequence of events.
+#[derive(Debug, Serialize)]
+pub struct Evaluation {
+    pub expected: Event,
+    /// Sorted by Q-value, best first.
+    pub details: Vec<Detail>,
+    pub shanten: i8,
+    pub at_furiten: bool,
+    pub model_tag: String,
 }
 
 #[derive(Deserialize)]
@@ -104,74 +191,141 @@ struct ExtraData {
     phi_matrix: Vec<[[f64; 4]; 4]>,
 }
 
+/// How close to Mortal's choice a different decision has to be to be
+/// tolerated.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tolerance {
+    /// At most this much lower in Q-value than the best action.
+    QGap(f64),
+    /// At least this ratio of the probability of the best action.
+    ProbRatio(f64),
+}
+
+impl Tolerance {
+    fn tolerates(self, best: &Detail, actual: &Detail) -> bool {
+        match self {
+            Self::QGap(gap) => (best.q_value - actual.q_value) as f64 <= gap,
+            Self::ProbRatio(ratio) => actual.prob as f64 >= best.prob as f64 * ratio,
+        }
+    }
+}
+
 pub struct Reviewer<'a> {
     pub mortal_exe: &'a Path,
     pub mortal_cfg: &'a Path,
     pub events: &'a [Event],
     pub player_id: u8,
     pub temperature: f32,
+    pub tolerance: Option<Tolerance>,
+    /// Review only these categories if not empty.
+    pub only: &'a [Category],
+    pub thresholds: Thresholds,
+    /// Number of the worst decisions to rank.
+    pub top_mistakes: usize,
     pub verbose: bool,
 }
 
+impl Review {
+    /// One row for each entry.
+    pub fn rows(&self, game_id: Option<&str>, seat: u8) -> Vec<Row> {
+        self.kyokus
+            .iter()
+            .flat_map(|k| {
+                k.entries.iter().map(move |e| Row {
+                    game_id: game_id.map(ToOwned::to_owned),
+                    seat,
+                    kyoku: export::kyoku_name(k.kyoku),
+                    honba: k.honba,
+                    junme: e.junme,
+                    tiles_left: e.tiles_left,
+                    category: e.category,
+                    severity: e.severity,
+                    shanten: Some(e.shanten),
+                    at_furiten: Some(e.at_furiten),
+                    actual: export::describe(slice::from_ref(&e.actual)),
+                    expected: export::describe(slice::from_ref(&e.expected)),
+                    is_equal: e.is_equal,
+                    is_tolerable: e.is_tolerable,
+                    actual_value: e.details.get(e.actual_index).map(|d| d.q_value.into()),
+                    expected_value: e.details.first().map(|d| d.q_value.into()),
+                    loss: e.loss,
+                })
+            })
+            .collect()
+    }
+}
+
 impl Reviewer<'_> {
-    pub fn review(&self) -> Result<Review> {
+    /// The command to spawn the engine with.
+    pub fn command(&self) -> Command {
         let &Self {
             mortal_exe,
             mortal_cfg,
-            events,
             player_id,
-            temperature,
             verbose,
+            ..
         } = self;
 
-        let mut kyoku_reviews = vec![];
-
         if verbose {
             log!("$ env MORTAL_REVIEW_MODE=1 MORTAL_CFG={mortal_cfg:?} {mortal_exe:?} {player_id}");
         }
 
-        let mut mortal = Command::new(mortal_exe)
+        let mut command = Command::new(mortal_exe);
+        command
             .arg(player_id.to_string())
             .env("MORTAL_REVIEW_MODE", "1")
-            .env("MORTAL_CFG", mortal_cfg)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::inherit())
-            .spawn()
-            .context("failed to spawn engine")?;
-
-        let mut stdin = mortal
-            .stdin
-            .take()
-            .context("failed to get stdin of engine")?;
-        let stdout = mortal
-            .stdout
-            .take()
-            .context("failed to get stdout of engine")?;
-        let mut stdout_lines = BufReader::new(stdout).lines();
+            .env("MORTAL_CFG", mortal_cfg);
+        command
+    }
+
+    pub fn review(&self, mortal: &mut Session) -> Result<Review> {
+        let &Self {
+            events,
+            player_id,
+            temperature,
+            tolerance,
+            only,
+            thresholds,
+            top_mistakes,
+            ..
+        } = self;
+
+        let mut kyoku_reviews = vec![];
 
         let events_len = events.len();
         let mut total_reviewed = 0;
         let mut total_matches = 0;
+        let mut total_tolerated = 0;
         let mut raw_rating = 0.;
+        let mut metrics = Accumulator::new(thresholds);
+        let mut breakdown = Breakdown::default();
+        let mut eval_times = vec![];
+        let mut non_greedy = 0;
+        let mut max_batch_size = None;
 
         let mut kyoku_review = KyokuReview::default();
         let mut state = State::new(player_id);
+        let mut table = Board::default();
         let mut junme = 0;
         let mut tiles_left = 70;
         let mut last_tsumo_or_discard = None;
         let mut last_actor = 0;
+        let mut facing_riichi = false;
         let mut entries = vec![];
 
         for (i, event) in events.iter().enumerate() {
             let to_write = json::to_string(event).unwrap();
-            writeln!(stdin, "{to_write}").context("failed to write to engine")?;
-            if verbose {
-                log!("> {to_write}");
-            }
+            mortal.send(&to_write).with_context(|| {
+                format!(
+                    "at kyoku {}, honba {}, event #{i}",
+                    kyoku_review.kyoku, kyoku_review.honba,
+                )
+            })?;
 
             // update the state
             state.update(event).context("failed to update state")?;
+            table.apply(event).context("failed to replay the table")?;
 
             let mut at_self_chi_pon = false;
             let mut at_self_riichi = false;
@@ -188,6 +342,7 @@ impl Reviewer<'_> {
                     kyoku_review.relative_scores = scores;
                     kyoku_review.relative_scores.rotate_left(player_id as usize);
                     tiles_left = 70;
+                    facing_riichi = false;
                 }
 
                 Event::EndKyoku => {
@@ -217,6 +372,10 @@ impl Reviewer<'_> {
                     at_self_riichi = true;
                 }
 
+                Event::ReachAccepted { actor } if actor != player_id => {
+                    facing_riichi = true;
+                }
+
                 Event::Dahai { pai, .. } | Event::Kakan { pai, .. } => {
                     last_tsumo_or_discard = Some(pai);
                 }
@@ -228,13 +387,12 @@ impl Reviewer<'_> {
                 last_actor = actor;
             }
 
-            let line = stdout_lines
-                .next()
-                .context("failed to read from engine: unexpected EOF")?
-                .context("failed to read from engine")?;
-            if verbose {
-                log!("< {line}");
-            }
+            let line = mortal.recv().with_context(|| {
+                format!(
+                    "failed to read from engine at kyoku {}, honba {}, event #{i}",
+                    kyoku_review.kyoku, kyoku_review.honba,
+                )
+            })?;
 
             if matches!(
                 event,
@@ -257,6 +415,23 @@ impl Reviewer<'_> {
                 json::from_str(&line).context("failed to parse JSON output of engine")?;
 
             let Some(meta) = output.meta else { continue };
+            let is_greedy = meta.is_greedy;
+            let batch_size = meta.batch_size;
+            let eval_time_ns = meta.eval_time_ns.map(|t| {
+                t + meta
+                    .kan_select
+                    .as_ref()
+                    .and_then(|k| k.eval_time_ns)
+                    .unwrap_or_default()
+            });
+            if let Some(t) = eval_time_ns {
+                eval_times.push(t);
+            }
+            if is_greedy == Some(false) {
+                non_greedy += 1;
+            }
+            max_batch_size = max_batch_size.max(batch_size);
+
             let mask_bits = meta.mask_bits.context("missing mask_bits")?;
             if mask_bits.count_ones() <= 1 {
                 // cannot act, or there is only one candidate
@@ -279,89 +454,31 @@ impl Reviewer<'_> {
             };
             let actual_label = to_label(&actual);
             ensure!(masks[actual_label], "{actual:?} is not a valid reaction");
-            let mut actual_q_value_opt = None;
-
-            let shanten = meta.shanten.context("missing shanten")?;
-            let at_furiten = meta.at_furiten.context("missing at_furiten")?;
-            let mut q_values = meta.q_values.context("missing q_values")?;
-            let mut details = Vec::with_capacity(q_values.len());
-            let mut min = f64::MAX;
-            let mut max = f64::MIN;
-            for (label, m) in masks.into_iter().enumerate().rev() {
-                if !m {
-                    continue;
-                }
-                let q_value = q_values.pop().context("q_values vec underflow")?;
-                min = min.min(q_value as f64);
-                max = max.max(q_value as f64);
 
-                let action = to_event(&state, label, last_actor, last_tsumo_or_discard, false)?;
-                if label == actual_label {
-                    actual_q_value_opt = Some(q_value as f64);
-                }
-
-                details.push(Detail {
-                    action,
-                    q_value,
-                    prob: 0., // will be calculated later
-                    label: Label::General(label),
-                });
+            let category = Situation {
+                own_turn: last_actor == player_id,
+                can_agari,
+                can_ryukyoku,
+                can_chi_pon: masks[38..=41].iter().any(|&m| m),
+                can_kan: masks[42],
+                can_riichi: masks[37],
+                at_self_riichi,
+                facing_riichi,
             }
-
-            let actual_kan_label = to_kan_label(&actual);
-            if let Some(kan_select) = meta.kan_select {
-                let mask_bits = kan_select.mask_bits.context("missing mask_bits")?;
-                let num_kans = mask_bits.count_ones();
-                ensure!(
-                    num_kans > 0,
-                    "expected `num_kans > 0`, got mask_bits = {mask_bits}",
-                );
-
-                let (orig_kan_idx, orig_kan_q_value) = details
-                    .iter()
-                    .enumerate()
-                    .find_map(|(i, d)| {
-                        matches!(d.action, Event::Ankan { .. }).then_some((i, d.q_value))
-                    })
-                    .context("in kan_select but no kan found in root")?;
-                details.remove(orig_kan_idx);
-
-                let masks = masks_from_bits(mask_bits);
-                let mut q_values = kan_select.q_values.context("missing q_values")?;
-                for (kan_label, m) in masks.into_iter().enumerate().rev() {
-                    if !m {
-                        continue;
-                    }
-                    let q_value = if num_kans == 1 {
-                        orig_kan_q_value
-                    } else {
-                        q_values.pop().context("q_values vec underflow")?
-                    };
-                    min = min.min(q_value as f64);
-                    max = max.max(q_value as f64);
-
-                    let action =
-                        to_event(&state, kan_label, last_actor, last_tsumo_or_discard, true)?;
-                    // I REALLY want to get rid of the rating and the table...
-                    if num_kans > 1 && matches!(actual_kan_label, Some(l) if l == kan_label) {
-                        actual_q_value_opt = Some(q_value as f64);
-                    }
-
-                    details.push(Detail {
-                        action,
-                        q_value,
-                        prob: 0., // will be calculated later
-                        label: Label::KanSelect(kan_label),
-                    });
-                }
+            .category();
+            if !only.is_empty() && !only.contains(&category) {
+                continue;
             }
 
-            let mut probs: Vec<_> = details.iter().map(|d| d.q_value).collect();
-            softmax(&mut probs, temperature);
-            details.iter_mut().zip(probs).for_each(|(d, v)| d.prob = v);
+            let shanten = meta.shanten.context("missing shanten")?;
+            let at_furiten = meta.at_furiten.context("missing at_furiten")?;
+            let Candidates {
+                details,
+                min_q,
+                max_q,
+            } = candidates(meta, &state, last_actor, last_tsumo_or_discard, temperature)?;
 
-            // this sort is better to be stable
-            details.sort_by(|l, r| r.q_value.total_cmp(&l.q_value));
+            let actual_kan_label = to_kan_label(&actual);
             let actual_index = details
                 .iter()
                 .enumerate()
@@ -375,48 +492,87 @@ impl Reviewer<'_> {
                     format!("failed to find action ({actual_label}, {actual_kan_label:?}) in details {details:?}")
                 })?;
 
-            let is_equal = equal_ignore_aka_consumed(&output.event, &actual);
-            let actual_q_value = actual_q_value_opt
-                .with_context(|| format!("failed to find q value of actual action {actual:?}"))?;
+            let is_equal = is_same_decision(&output.event, &actual);
+            let is_tolerable = !is_equal
+                && tolerance.is_some_and(|t| t.tolerates(&details[0], &details[actual_index]));
+            let actual_q_value = details[actual_index].q_value as f64;
             if is_equal {
                 raw_rating += 1.;
                 total_matches += 1;
             } else {
-                raw_rating += (actual_q_value - min) / (max - min).max(1e-6);
+                raw_rating += (actual_q_value - min_q) / (max_q - min_q).max(1e-6);
+            }
+            if is_tolerable {
+                total_tolerated += 1;
             }
             total_reviewed += 1;
+            let loss = if is_equal {
+                Some(0.)
+            } else {
+                Some(details[0].q_value as f64 - actual_q_value)
+            };
+            let sample = Sample {
+                is_equal,
+                is_tolerable,
+                rank: Some(actual_index),
+                loss,
+                prob: Some(details[actual_index].prob as f64),
+            };
+            metrics.push(&sample);
+            breakdown.push(category, &sample);
+            let severity = thresholds.severity(is_equal || is_tolerable, loss);
 
             let tile = last_tsumo_or_discard.context("missing last tsumo or discard")?;
             let at_opponent_kakan = matches!(event, Event::Kakan { .. });
+            // a riichi is declared with the next discard
+            let discard = match actual {
+                Event::Dahai { pai, .. } => Some(pai),
+                Event::Reach { .. } => events[i + 1..].iter().find_map(|ev| match *ev {
+                    Event::Dahai { actor, pai, .. } if actor == player_id => Some(pai),
+                    _ => None,
+                }),
+                _ => None,
+            };
+            let hindsight = hindsight::hindsight(&table, player_id, discard, &events[i + 1..]);
+            let expected_discard = match output.event {
+                Event::Dahai { pai, .. } => Some(pai),
+                _ => None,
+            };
+            let safety = Safety::new(&state, discard, expected_discard);
 
             let entry = Entry {
+                event_index: i,
                 junme,
                 tiles_left,
                 last_actor,
                 tile,
-                state: state.clone(),
+                category,
+                severity,
+                loss,
+                state: state.snapshot(),
                 at_self_chi_pon,
                 at_self_riichi,
                 at_opponent_kakan,
                 expected: output.event,
                 actual,
                 is_equal,
+                is_tolerable,
                 details,
                 shanten,
                 at_furiten,
                 actual_index,
+                is_greedy,
+                batch_size,
+                eval_time_ns,
+                hindsight,
+                safety,
             };
             entries.push(entry);
         }
-        drop(stdin);
-
-        let line = stdout_lines
-            .next()
-            .context("failed to read from engine: unexpected EOF")?
-            .context("failed to read from engine")?;
-        if verbose {
-            log!("< {line}");
-        }
+        mortal.close_stdin();
+        let line = mortal
+            .recv()
+            .context("failed to read extra data from engine")?;
 
         let ExtraData {
             model_tag,
@@ -428,56 +584,202 @@ impl Reviewer<'_> {
             k.rotate_left(player_id as usize);
         }
 
-        let status = mortal.wait()?;
-        if !status.success() {
-            if let Some(code) = status.code() {
-                bail!("non-zero exit code: {code}");
-            }
-            bail!("process terminated by signal");
+        let performance = Performance::new(eval_times, non_greedy, max_batch_size);
+        if performance.non_greedy > 0 {
+            log!(
+                "WARNING: {} decisions of Mortal were not greedy, check the config",
+                performance.non_greedy,
+            );
         }
 
-        let rating = (raw_rating / total_reviewed as f64).powi(2);
+        let rating = (total_reviewed > 0).then(|| (raw_rating / total_reviewed as f64).powi(2));
         Ok(Review {
             total_reviewed,
             total_matches,
+            total_tolerated,
+            tolerance,
             rating,
+            metrics: metrics.finish(),
+            categories: breakdown.finish(),
+            top_mistakes: metrics::top_mistakes(
+                kyoku_reviews.iter().enumerate().flat_map(|(k, kyoku)| {
+                    kyoku
+                        .entries
+                        .iter()
+                        .enumerate()
+                        .map(move |(e, entry)| (k, e, entry.severity, entry.loss))
+                }),
+                top_mistakes,
+            ),
+            performance,
             temperature,
             kyokus: kyoku_reviews,
             relative_phi_matrix: phi_matrix,
             model_tag,
         })
     }
-}
 
-fn masks_from_bits(bits: u64) -> [bool; 46] {
-    array::from_fn(|i| (bits >> i) & 0b1 == 0b1)
+    /// Feeds all the events to the engine and evaluates the decision after the
+    /// last one, which must be a decision of the player to review.
+    pub fn evaluate_last(&self, mortal: &mut Session) -> Result<Evaluation> {
+        let &Self {
+            events,
+            player_id,
+            temperature,
+            ..
+        } = self;
+
+        let mut state = State::new(player_id);
+        let mut last_tsumo_or_discard = None;
+        let mut last_actor = 0;
+        let mut line = String::new();
+        for (i, event) in events.iter().enumerate() {
+            let to_write = json::to_string(event).unwrap();
+            mortal
+                .send(&to_write)
+                .with_context(|| format!("at event #{i}"))?;
+            state.update(event).context("failed to update state")?;
+            match *event {
+                Event::Tsumo { actor, pai } if actor == player_id => {
+                    last_tsumo_or_discard = Some(pai);
+                }
+                Event::Dahai { pai, .. } | Event::Kakan { pai, .. } => {
+                    last_tsumo_or_discard = Some(pai);
+                }
+                _ => (),
+            }
+            if let Some(actor) = event.actor() {
+                last_actor = actor;
+            }
+            line = mortal
+                .recv()
+                .with_context(|| format!("failed to read from engine at event #{i}"))?;
+        }
+        mortal.close_stdin();
+        let extra = mortal
+            .recv()
+            .context("failed to read extra data from engine")?;
+        let ExtraData { model_tag, .. } =
+            json::from_str(&extra).context("failed to parse JSON output of engine")?;
+
+        let output: RawAction =
+            json::from_str(&line).context("failed to parse JSON output of engine")?;
+        let meta = output.meta.context("there is nothing to decide")?;
+        let shanten = meta.shanten.context("missing shanten")?;
+        let at_furiten = meta.at_furiten.context("missing at_furiten")?;
+        let Candidates { details, .. } =
+            candidates(meta, &state, last_actor, last_tsumo_or_discard, temperature)?;
+
+        Ok(Evaluation {
+            expected: output.event,
+            details,
+            shanten,
+            at_furiten,
+            model_tag,
+        })
+    }
 }
 
-/// It assumes they have the same actor.
-fn equal_ignore_aka_consumed(a: &Event, b: &Event) -> bool {
-    match (a, b) {
-        (Event::Dahai { pai: l, .. }, Event::Dahai { pai: r, .. })
-        | (Event::Kakan { pai: l, .. }, Event::Kakan { pai: r, .. }) => l == r,
+struct Candidates {
+    /// Sorted by Q-value, best first.
+    details: Vec<Detail>,
+    /// Range of all Q-values, including the one of the kan before kan select.
+    min_q: f64,
+    max_q: f64,
+}
 
-        (Event::Chi { consumed: l, .. }, Event::Chi { consumed: r, .. })
-        | (Event::Pon { consumed: l, .. }, Event::Pon { consumed: r, .. }) => {
-            tile_set_eq(l, r, true)
+/// Lists every action allowed by `meta` with its Q-value and probability.
+fn candidates(
+    meta: Metadata,
+    state: &State,
+    last_actor: u8,
+    last_tsumo_or_discard: Option<Tile>,
+    temperature: f32,
+) -> Result<Candidates> {
+    let masks = masks_from_bits(meta.mask_bits.context("missing mask_bits")?);
+    let mut q_values = meta.q_values.context("missing q_values")?;
+    let mut details = Vec::with_capacity(q_values.len());
+    let mut min = f64::MAX;
+    let mut max = f64::MIN;
+    for (label, m) in masks.into_iter().enumerate().rev() {
+        if !m {
+            continue;
         }
+        let q_value = q_values.pop().context("q_values vec underflow")?;
+        min = min.min(q_value as f64);
+        max = max.max(q_value as f64);
+
+        let action = to_event(state, label, last_actor, last_tsumo_or_discard, false)?;
+        details.push(Detail {
+            action,
+            q_value,
+            prob: 0., // will be calculated later
+            label: Label::General(label),
+        });
+    }
 
-        (Event::Daiminkan { consumed: l, .. }, Event::Daiminkan { consumed: r, .. }) => {
-            tile_set_eq(l, r, true)
-        }
-        (Event::Ankan { consumed: l, .. }, Event::Ankan { consumed: r, .. }) => {
-            tile_set_eq(l, r, true)
+    if let Some(kan_select) = meta.kan_select {
+        let mask_bits = kan_select.mask_bits.context("missing mask_bits")?;
+        let num_kans = mask_bits.count_ones();
+        ensure!(
+            num_kans > 0,
+            "expected `num_kans > 0`, got mask_bits = {mask_bits}",
+        );
+
+        let (orig_kan_idx, orig_kan_q_value) = details
+            .iter()
+            .enumerate()
+            .find_map(|(i, d)| matches!(d.label, Label::General(42)).then_some((i, d.q_value)))
+            .context("in kan_select but no kan found in root")?;
+        details.remove(orig_kan_idx);
+
+        let masks = masks_from_bits(mask_bits);
+        let mut q_values = kan_select.q_values.context("missing q_values")?;
+        for (kan_label, m) in masks.into_iter().enumerate().rev() {
+            if !m {
+                continue;
+            }
+            let q_value = if num_kans == 1 {
+                orig_kan_q_value
+            } else {
+                q_values.pop().context("q_values vec underflow")?
+            };
+            min = min.min(q_value as f64);
+            max = max.max(q_value as f64);
+
+            let action = to_event(state, kan_label, last_actor, last_tsumo_or_discard, true)?;
+            details.push(Detail {
+                action,
+                q_value,
+                prob: 0., // will be calculated later
+                label: Label::KanSelect(kan_label),
+            });
         }
+    }
 
-        (Event::Reach { .. }, Event::Reach { .. })
-        | (Event::Hora { .. }, Event::Hora { .. })
-        | (Event::Ryukyoku { .. }, Event::Ryukyoku { .. })
-        | (Event::None, Event::None) => true,
+    let mut probs: Vec<_> = details.iter().map(|d| d.q_value).collect();
+    softmax(&mut probs, temperature);
+    details.iter_mut().zip(probs).for_each(|(d, v)| d.prob = v);
 
-        _ => false,
-    }
+    // this sort is better to be stable
+    details.sort_by(|l, r| r.q_value.total_cmp(&l.q_value));
+
+    Ok(Candidates {
+        details,
+        min_q: min,
+        max_q: max,
+    })
+}
+
+fn masks_from_bits(bits: u64) -> [bool; 46] {
+    array::from_fn(|i| (bits >> i) & 0b1 == 0b1)
+}
+
+/// Whether they take the same label of Mortal, ignoring which aka tiles a
+/// call consumes. Mortal has one label per tile kind for Chi, Pon and kans,
+/// so it has no preference between consuming the aka or keeping it.
+fn is_same_decision(a: &Event, b: &Event) -> bool {
+    to_label(a) == to_label(b) && to_kan_label(a) == to_kan_label(b)
 }
 
 fn to_label(ev: &Event) -> usize {
@@ -515,12 +817,13 @@ const fn to_kan_label(ev: &Event) -> Option<usize> {
     }
 }
 
-/// Important note:
+/// Reconstructs the action of `label` from the hand of the player.
 ///
-/// * For Chi and Pon, `consumed` are always deaka'd, which is different from
-///   Mortal's behavior.
-/// * There is no Kakan or Daiminkan, all kans will be returned as Ankan. They
-///   are the same on the UI anyways.
+/// * For Chi, Pon and Daiminkan, `target` and `last_tsumo_or_discard` are the
+///   discard being called. Aka tiles in the hand are consumed first, as Mortal
+///   does.
+/// * For kans on the player's own turn, `label` is the tile of the kan if
+///   `at_kan_select`, otherwise the first possible kan is taken.
 fn to_event(
     state: &State,
     label: usize,
@@ -532,12 +835,28 @@ fn to_event(
 
     if at_kan_select {
         ensure!(label < 34, "invalid kan label {label}");
-        let tile = must_tile!(label);
-        let consumed = [tile; 4];
-        let event = Event::Ankan { actor, consumed };
-        return Ok(event);
+        return own_kan(state, must_tile!(label))
+            .with_context(|| format!("no kan of {} in {state:?}", must_tile!(label)));
     }
 
+    // takes one tile of each kind from the hand
+    let consume = |kinds: [Tile; 2]| -> Result<[Tile; 2]> {
+        let mut consumed = kinds;
+        let mut taken = vec![];
+        for t in &mut consumed {
+            let like = state.tiles_like(*t);
+            let skip = taken
+                .iter()
+                .filter(|&&x: &&Tile| x.deaka() == t.deaka())
+                .count();
+            *t = *like
+                .get(skip)
+                .with_context(|| format!("no {t} to consume in {state:?}"))?;
+            taken.push(*t);
+        }
+        Ok(consumed)
+    };
+
     let event = match label {
         0..=36 => Event::Dahai {
             actor,
@@ -545,92 +864,47 @@ fn to_event(
             tsumogiri: last_tsumo_or_discard.is_some_and(|t| t.as_usize() == label),
         },
         37 => Event::Reach { actor },
-        38 => {
+        38..=40 => {
             let pai = last_tsumo_or_discard.context("missing last discard for Chi")?;
-            let can_akaize_consumed = match pai.as_u8() {
-                tu8!(3m) | tu8!(4m) => state.has_tile(t!(5mr)),
-                tu8!(3p) | tu8!(4p) => state.has_tile(t!(5pr)),
-                tu8!(3s) | tu8!(4s) => state.has_tile(t!(5sr)),
-                _ => false,
-            };
-            let consumed = if can_akaize_consumed {
-                [pai.next().akaize(), pai.next().next().akaize()]
-            } else {
-                [pai.next(), pai.next().next()]
+            let p = pai.deaka();
+            let kinds = match label {
+                38 => [p.next(), p.next().next()],
+                39 => [p.prev(), p.next()],
+                _ => [p.prev().prev(), p.prev()],
             };
             Event::Chi {
                 actor,
                 target,
                 pai,
-                consumed,
+                consumed: consume(kinds)?,
             }
         }
-        39 => {
-            let pai = last_tsumo_or_discard.context("missing last discard for Chi")?;
-            let can_akaize_consumed = match pai.as_u8() {
-                tu8!(4m) | tu8!(6m) => state.has_tile(t!(5mr)),
-                tu8!(4p) | tu8!(6p) => state.has_tile(t!(5pr)),
-                tu8!(4s) | tu8!(6s) => state.has_tile(t!(5sr)),
-                _ => false,
-            };
-            let consumed = if can_akaize_consumed {
-                [pai.prev().akaize(), pai.next().akaize()]
-            } else {
-                [pai.prev(), pai.next()]
-            };
-            Event::Chi {
-                actor,
-                target,
-                pai,
-                consumed,
-            }
-        }
-        40 => {
-            let pai = last_tsumo_or_discard.context("missing last discard for Chi")?;
-            let can_akaize_consumed = match pai.as_u8() {
-                tu8!(6m) | tu8!(7m) => state.has_tile(t!(5mr)),
-                tu8!(6p) | tu8!(7p) => state.has_tile(t!(5pr)),
-                tu8!(6s) | tu8!(7s) => state.has_tile(t!(5sr)),
-                _ => false,
-            };
-            let consumed = if can_akaize_consumed {
-                [pai.prev().prev().akaize(), pai.prev().akaize()]
-            } else {
-                [pai.prev().prev(), pai.prev()]
-            };
-            Event::Chi {
+        41 => {
+            let pai = last_tsumo_or_discard.context("missing last discard for Pon")?;
+            Event::Pon {
                 actor,
                 target,
                 pai,
-                consumed,
+                consumed: consume([pai.deaka(); 2])?,
             }
         }
-        41 => {
-            let pai = last_tsumo_or_discard.context("missing last discard for Pon")?;
-            let can_akaize_consumed = match pai.as_u8() {
-                tu8!(5m) => state.has_tile(t!(5mr)),
-                tu8!(5p) => state.has_tile(t!(5pr)),
-                tu8!(5s) => state.has_tile(t!(5sr)),
-                _ => false,
-            };
-            let consumed = if can_akaize_consumed {
-                [pai.akaize(), pai.deaka()]
-            } else {
-                [pai.deaka(); 2]
-            };
-            Event::Pon {
+        42 if target != actor => {
+            let pai = last_tsumo_or_discard.context("missing last discard for Daiminkan")?;
+            let consumed = state
+                .tiles_like(pai)
+                .try_into()
+                .ok()
+                .with_context(|| format!("no Daiminkan of {pai} in {state:?}"))?;
+            Event::Daiminkan {
                 actor,
                 target,
                 pai,
                 consumed,
             }
         }
-        42 => {
-            // in fact this is Daiminkan
-            let tile = last_tsumo_or_discard.context("missing last discard for Daiminkan")?;
-            let consumed = [tile, tile.deaka(), tile.deaka(), tile.deaka()];
-            Event::Ankan { actor, consumed }
-        }
+        42 => (0..34)
+            .find_map(|kind: usize| own_kan(state, must_tile!(kind)))
+            .with_context(|| format!("no kan in {state:?}"))?,
         43 => Event::Hora {
             actor,
             target,
@@ -645,6 +919,22 @@ fn to_event(
     Ok(event)
 }
 
+/// Kakan or Ankan of `tile` on the player's own turn, if possible.
+fn own_kan(state: &State, tile: Tile) -> Option<Event> {
+    let actor = state.player_id();
+    let tiles = state.tiles_like(tile);
+    if let Some(consumed) = state.pon_of(tile) {
+        let &pai = tiles.first()?;
+        return Some(Event::Kakan {
+            actor,
+            pai,
+            consumed,
+        });
+    }
+    let consumed = tiles.try_into().ok()?;
+    Some(Event::Ankan { actor, consumed })
+}
+
 /// Get actual action from player's perspective, which will handle Event::None
 /// and multiple Event::Hora properly.
 ///
@@ -702,3 +992,71 @@ fn next_action(
         },
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use convlog::t;
+
+    fn state(events: &[&str]) -> State {
+        let mut state = State::new(0);
+        for ev in events {
+            state.update(&json::from_str(ev).unwrap()).unwrap();
+        }
+        state
+    }
+
+    #[test]
+    fn reconstruct_actions() {
+        let start = r#"{"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["3m","4m","5mr","5m","5m","6m","7p","7p","7p","E","E","S","S"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}"#;
+        let s = state(&[start]);
+
+        // aka are consumed first
+        let chi = to_event(&s, 39, 3, Some(t!(4m)), false).unwrap();
+        assert!(matches!(chi, Event::Chi { consumed, .. } if consumed == [t!(3m), t!(5mr)]));
+        let pon = to_event(&s, 41, 2, Some(t!(5m)), false).unwrap();
+        assert!(matches!(pon, Event::Pon { consumed, .. } if consumed == [t!(5mr), t!(5m)]));
+        let kan = to_event(&s, 42, 2, Some(t!(5m)), false).unwrap();
+        assert!(
+            matches!(kan, Event::Daiminkan { target: 2, consumed, .. } if consumed == [t!(5mr), t!(5m), t!(5m)])
+        );
+        to_event(&s, 42, 2, Some(t!(6m)), false).unwrap_err();
+
+        let s = state(&[
+            start,
+            r#"{"type":"tsumo","actor":0,"pai":"7p"}"#,
+            r#"{"type":"dahai","actor":0,"pai":"S","tsumogiri":false}"#,
+            r#"{"type":"pon","actor":0,"target":1,"pai":"E","consumed":["E","E"]}"#,
+            r#"{"type":"dahai","actor":0,"pai":"S","tsumogiri":false}"#,
+            r#"{"type":"tsumo","actor":0,"pai":"E"}"#,
+        ]);
+        let ankan = to_event(&s, tu8!(7p) as usize, 0, Some(t!(E)), true).unwrap();
+        assert!(matches!(ankan, Event::Ankan { consumed, .. } if consumed == [t!(7p); 4]));
+        let kakan = to_event(&s, tu8!(E) as usize, 0, Some(t!(E)), true).unwrap();
+        assert!(matches!(kakan, Event::Kakan { pai, .. } if pai == t!(E)));
+        assert!(is_same_decision(&kakan, &kakan));
+        assert!(!is_same_decision(&ankan, &kakan));
+        // pon keeping the aka
+        assert!(is_same_decision(
+            &pon,
+            &Event::Pon {
+                actor: 0,
+                target: 2,
+                pai: t!(5m),
+                consumed: [t!(5m); 2],
+            }
+        ));
+        assert!(!is_same_decision(
+            &Event::Dahai {
+                actor: 0,
+                pai: t!(5mr),
+                tsumogiri: false,
+            },
+            &Event::Dahai {
+                actor: 0,
+                pai: t!(5m),
+                tsumogiri: false,
+            },
+        ));
+    }
+}
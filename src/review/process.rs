@@ -0,0 +1,431 @@
+use crate::log;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::io::prelude::*;
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+/// Number of stderr lines of the engine included in crash reports.
+const STDERR_TAIL_LINES: usize = 20;
+
+/// Number of stderr lines of the engine kept in `EngineLog`, beyond which the
+/// earliest ones are dropped.
+const ENGINE_LOG_LINES: usize = 10_000;
+
+/// How often a blocking read wakes up to check for Ctrl-C.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Installs a Ctrl-C handler, after which any pending read from an engine
+/// fails and the engine gets killed instead of the whole process exiting
+/// right away.
+pub fn install_interrupt_handler() -> Result<()> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            // second Ctrl-C, give up being graceful
+            std::process::exit(130);
+        }
+        log!("interrupted, shutting down engine...");
+    })
+    .context("failed to set Ctrl-C handler")
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Watchdog {
+    /// Max time to wait for a single line from the engine.
+    pub response_timeout: Option<Duration>,
+    /// Max time for the whole session, including retries.
+    pub total_timeout: Option<Duration>,
+    /// How many times to restart a crashed or hung engine.
+    pub retries: u32,
+}
+
+/// A line the engine wrote to stderr.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StderrLine {
+    /// Index of the last line sent to the engine before this was read, which
+    /// is the event in flight. `None` if nothing had been sent.
+    pub event: Option<usize>,
+    pub line: String,
+}
+
+/// Everything the engine wrote to stderr, across restarts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EngineLog {
+    pub lines: VecDeque<StderrLine>,
+    /// Number of the earliest lines dropped to bound the memory used.
+    pub dropped: usize,
+}
+
+impl EngineLog {
+    fn push(&mut self, line: StderrLine) {
+        if self.lines.len() == ENGINE_LOG_LINES {
+            self.lines.pop_front();
+            self.dropped += 1;
+        }
+        self.lines.push_back(line);
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut file = File::create(path)
+            .with_context(|| format!("failed to create diagnostics file {}", path.display()))?;
+        file.write_all(self.to_string().as_bytes())
+            .with_context(|| format!("failed to write diagnostics file {}", path.display()))
+    }
+}
+
+/// One line per line of stderr, prefixed with the index of the event in
+/// flight like "#42\t".
+impl fmt::Display for EngineLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.dropped > 0 {
+            writeln!(f, "({} earlier lines dropped)", self.dropped)?;
+        }
+        for StderrLine { event, line } in &self.lines {
+            match event {
+                Some(i) => writeln!(f, "#{i}\t{line}")?,
+                None => writeln!(f, "-\t{line}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A running engine speaking a line based protocol over stdio.
+///
+/// Every line sent is kept, so that when the engine crashes or hangs it can be
+/// restarted and fed with the same lines again, skipping the responses that
+/// have already been read.
+pub struct EngineProcess {
+    command: Command,
+    watchdog: Watchdog,
+    deadline: Option<Instant>,
+    retries_left: u32,
+    verbose: bool,
+
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout_rx: Receiver<String>,
+    stderr_thread: JoinHandle<()>,
+    log: Arc<Mutex<EngineLog>>,
+    /// Mirrors the length of `sent` for the stderr thread.
+    sent_count: Arc<AtomicUsize>,
+
+    sent: Vec<String>,
+    received: usize,
+    stdin_closed: bool,
+}
+
+impl EngineProcess {
+    /// Spawns the engine. Stdio settings of `command` are overridden.
+    pub fn spawn(command: Command, watchdog: Watchdog, verbose: bool) -> Result<Self> {
+        let log = Arc::default();
+        let sent_count = Arc::default();
+        let (child, stdin, stdout_rx, stderr_thread) = start(&command, &log, &sent_count, verbose)?;
+        Ok(Self {
+            deadline: watchdog.total_timeout.map(|t| Instant::now() + t),
+            retries_left: watchdog.retries,
+            command,
+            watchdog,
+            verbose,
+            child,
+            stdin: Some(stdin),
+            stdout_rx,
+            stderr_thread,
+            log,
+            sent_count,
+            sent: vec![],
+            received: 0,
+            stdin_closed: false,
+        })
+    }
+
+    /// Writes a line to the engine.
+    pub fn send(&mut self, line: &str) -> Result<()> {
+        self.sent.push(line.to_owned());
+        self.sent_count.store(self.sent.len(), Ordering::SeqCst);
+        if self.verbose {
+            log!("> {line}");
+        }
+        if let Err(err) = self.write_line_raw(line) {
+            self.recover(err.context("failed to write to engine"))?;
+        }
+        Ok(())
+    }
+
+    /// Reads a line from the engine, restarting it if it crashed or timed out
+    /// and there are retries left.
+    pub fn recv(&mut self) -> Result<String> {
+        loop {
+            match self.read_line() {
+                Ok(line) => {
+                    self.received += 1;
+                    if self.verbose {
+                        log!("< {}", line.trim());
+                    }
+                    return Ok(line);
+                }
+                Err(err) => self.recover(err)?,
+            }
+        }
+    }
+
+    /// Closes stdin of the engine, which is the signal for it to finish.
+    pub fn close_stdin(&mut self) {
+        self.stdin_closed = true;
+        self.stdin = None;
+    }
+
+    /// Closes stdin, waits for the engine to exit successfully and returns
+    /// what it wrote to stderr.
+    pub fn finish(mut self) -> Result<EngineLog> {
+        self.close_stdin();
+
+        let status = loop {
+            if let Some(status) = self.child.try_wait()? {
+                break status;
+            }
+            self.check_deadline()
+                .with_context(|| self.crash_report("engine did not exit"))?;
+            thread::sleep(POLL_INTERVAL);
+        };
+        check_status(status).with_context(|| self.crash_report("engine exited abnormally"))?;
+
+        // let the rest of stderr be read, unless something else holds it open
+        for _ in 0..10 {
+            if self.stderr_thread.is_finished() {
+                break;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        Ok(self.log.lock().unwrap().clone())
+    }
+
+    /// The last lines the engine wrote to stderr.
+    pub fn stderr_tail(&self) -> Vec<String> {
+        let log = self.log.lock().unwrap();
+        let skip = log.lines.len().saturating_sub(STDERR_TAIL_LINES);
+        log.lines
+            .iter()
+            .skip(skip)
+            .map(|l| l.line.clone())
+            .collect()
+    }
+
+    fn write_line_raw(&mut self, line: &str) -> Result<()> {
+        let stdin = self.stdin.as_mut().context("stdin of engine is closed")?;
+        writeln!(stdin, "{line}")?;
+        Ok(())
+    }
+
+    fn read_line(&mut self) -> Result<String> {
+        let started = Instant::now();
+        loop {
+            if INTERRUPTED.load(Ordering::SeqCst) {
+                bail!("interrupted by user");
+            }
+            self.check_deadline()?;
+            if let Some(timeout) = self.watchdog.response_timeout
+                && started.elapsed() >= timeout
+            {
+                bail!(
+                    "engine did not respond within {}",
+                    humantime::format_duration(timeout),
+                );
+            }
+
+            match self.stdout_rx.recv_timeout(POLL_INTERVAL) {
+                Ok(line) => return Ok(line),
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => {
+                    // give it a moment to actually exit so that the exit
+                    // status can be included
+                    for _ in 0..10 {
+                        if let Some(status) = self.child.try_wait()? {
+                            check_status(status).context("unexpected EOF")?;
+                            break;
+                        }
+                        thread::sleep(POLL_INTERVAL);
+                    }
+                    bail!("unexpected EOF");
+                }
+            }
+        }
+    }
+
+    fn check_deadline(&self) -> Result<()> {
+        if let Some(deadline) = self.deadline
+            && Instant::now() >= deadline
+        {
+            let total = self.watchdog.total_timeout.unwrap_or_default();
+            bail!(
+                "review did not finish within {}",
+                humantime::format_duration(total),
+            );
+        }
+        Ok(())
+    }
+
+    /// Either restarts the engine and replays everything sent so far, or
+    /// returns `err` decorated with a crash report.
+    fn recover(&mut self, mut err: anyhow::Error) -> Result<()> {
+        loop {
+            let interrupted = INTERRUPTED.load(Ordering::SeqCst);
+            if interrupted || self.retries_left == 0 || self.check_deadline().is_err() {
+                let report = self.crash_report("engine failed");
+                return Err(err.context(report));
+            }
+            self.retries_left -= 1;
+            log!(
+                "WARNING: {err:#}, restarting engine and replaying {} lines ({} retries left)",
+                self.sent.len(),
+                self.retries_left,
+            );
+
+            match self.restart() {
+                Ok(()) => return Ok(()),
+                Err(e) => err = e.context("failed to replay"),
+            }
+        }
+    }
+
+    fn restart(&mut self) -> Result<()> {
+        self.kill();
+        let (child, stdin, stdout_rx, stderr_thread) =
+            start(&self.command, &self.log, &self.sent_count, self.verbose)?;
+        self.child = child;
+        self.stdin = Some(stdin);
+        self.stdout_rx = stdout_rx;
+        self.stderr_thread = stderr_thread;
+
+        let stdin = self.stdin.as_mut().context("stdin of engine is closed")?;
+        for line in &self.sent {
+            writeln!(stdin, "{line}")?;
+        }
+        if self.stdin_closed {
+            self.stdin = None;
+        }
+        for _ in 0..self.received {
+            self.read_line()?;
+        }
+        Ok(())
+    }
+
+    fn crash_report(&self, msg: &str) -> String {
+        let tail = self.stderr_tail();
+        let mut report = format!(
+            "{msg} after {} lines sent and {} lines received",
+            self.sent.len(),
+            self.received,
+        );
+        if !tail.is_empty() {
+            report.push_str(", last lines of stderr:");
+            for line in tail {
+                report.push_str("\n    ");
+                report.push_str(&line);
+            }
+        }
+        report
+    }
+
+    fn kill(&mut self) {
+        self.stdin = None;
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+impl Drop for EngineProcess {
+    fn drop(&mut self) {
+        // a no-op if it has already exited
+        self.kill();
+    }
+}
+
+type Started = (Child, ChildStdin, Receiver<String>, JoinHandle<()>);
+
+/// Spawns the engine, with its stderr read into `log` on a background thread.
+fn start(
+    template: &Command,
+    log: &Arc<Mutex<EngineLog>>,
+    sent_count: &Arc<AtomicUsize>,
+    verbose: bool,
+) -> Result<Started> {
+    let mut command = Command::new(template.get_program());
+    command.args(template.get_args());
+    for (k, v) in template.get_envs() {
+        match v {
+            Some(v) => command.env(k, v),
+            None => command.env_remove(k),
+        };
+    }
+    if let Some(dir) = template.get_current_dir() {
+        command.current_dir(dir);
+    }
+
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to spawn engine")?;
+
+    let stdin = child
+        .stdin
+        .take()
+        .context("failed to get stdin of engine")?;
+    let stdout = child
+        .stdout
+        .take()
+        .context("failed to get stdout of engine")?;
+    let stderr = child
+        .stderr
+        .take()
+        .context("failed to get stderr of engine")?;
+
+    let (tx, stdout_rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            let Ok(line) = line else { break };
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let log = Arc::clone(log);
+    let sent_count = Arc::clone(sent_count);
+    let stderr_thread = thread::spawn(move || {
+        for line in BufReader::new(stderr).lines() {
+            let Ok(line) = line else { break };
+            let event = sent_count.load(Ordering::SeqCst).checked_sub(1);
+            if verbose {
+                log!("! {line}");
+            }
+            log.lock().unwrap().push(StderrLine { event, line });
+        }
+    });
+
+    Ok((child, stdin, stdout_rx, stderr_thread))
+}
+
+fn check_status(status: ExitStatus) -> Result<()> {
+    if !status.success() {
+        if let Some(code) = status.code() {
+            bail!("non-zero exit code: {code}");
+        }
+        bail!("process terminated by signal");
+    }
+    Ok(())
+}
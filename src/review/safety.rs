@@ -0,0 +1,189 @@
+use crate::state::State;
+use convlog::{Tile, must_tile};
+
+use serde::{Deserialize, Serialize};
+
+/// How safe a tile is against a riichi, judged by the visible tiles only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SafetyClass {
+    /// Discarded by the riichi player, or passed by it since the riichi.
+    Genbutsu,
+    /// Every ryanmen wait on it is ruled out by genbutsu.
+    Suji,
+    /// Every ryanmen wait on it needs a tile of which all 4 are visible.
+    NoChance,
+    /// Every ryanmen wait on it needs a tile of which 3 are visible.
+    OneChance,
+    /// With the number of the same tile visible to the player.
+    Honor {
+        visible: u8,
+    },
+    Unsafe,
+}
+
+impl SafetyClass {
+    /// Lower is safer. An honor with 3 visible can only be a tanki wait, which
+    /// is nearly as safe as genbutsu.
+    pub const fn rank(self) -> u8 {
+        match self {
+            Self::Genbutsu => 0,
+            Self::Honor { visible: 3.. } => 1,
+            Self::Suji | Self::NoChance | Self::Honor { visible: 2 } => 2,
+            Self::OneChance | Self::Honor { .. } => 3,
+            Self::Unsafe => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TileSafety {
+    pub tile: Tile,
+    pub class: SafetyClass,
+}
+
+/// Safety of the tiles in hand against one opponent in riichi.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Threat {
+    pub seat: u8,
+    /// One for each kind of tile in hand.
+    pub tiles: Vec<TileSafety>,
+    pub actual: Option<SafetyClass>,
+    pub expected: Option<SafetyClass>,
+}
+
+/// Safety of the discards against every opponent in riichi, along with the
+/// ones of the actual and the engine's discard.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Safety {
+    pub threats: Vec<Threat>,
+    pub actual: Option<Tile>,
+    pub expected: Option<Tile>,
+    /// The actual discard is less safe than the engine's against any threat.
+    pub is_less_safe: bool,
+}
+
+impl Safety {
+    /// Returns `None` if no opponent is in riichi.
+    pub fn new(state: &State, actual: Option<Tile>, expected: Option<Tile>) -> Option<Self> {
+        let board = state.board();
+        let visible = state.visible_counts();
+        let counts = state.tehai().counts34();
+        let kinds: Vec<_> = (0..34)
+            .filter(|&id| counts[id] > 0)
+            .map(|id| must_tile!(id))
+            .collect();
+
+        let threats: Vec<_> = (0..4)
+            .filter(|&seat| seat != state.player_id())
+            .filter(|&seat| board.seats[seat as usize].riichi_at.is_some())
+            .map(|seat| {
+                let s = &board.seats[seat as usize];
+                let mut genbutsu = [false; 34];
+                for t in s
+                    .river
+                    .iter()
+                    .map(|d| d.pai)
+                    .chain(s.passed_after_riichi.iter().copied())
+                {
+                    genbutsu[t.deaka().as_usize()] = true;
+                }
+                let class_of = |t: Tile| classify(t, &genbutsu, &visible);
+                Threat {
+                    seat,
+                    tiles: kinds
+                        .iter()
+                        .map(|&tile| TileSafety {
+                            tile,
+                            class: class_of(tile),
+                        })
+                        .collect(),
+                    actual: actual.map(class_of),
+                    expected: expected.map(class_of),
+                }
+            })
+            .collect();
+        if threats.is_empty() {
+            return None;
+        }
+
+        let is_less_safe = threats.iter().any(|t| match (t.actual, t.expected) {
+            (Some(a), Some(e)) => a.rank() > e.rank(),
+            _ => false,
+        });
+        Some(Self {
+            threats,
+            actual,
+            expected,
+            is_less_safe,
+        })
+    }
+}
+
+fn classify(tile: Tile, genbutsu: &[bool; 34], visible: &[u8; 34]) -> SafetyClass {
+    let id = tile.deaka().as_usize();
+    if genbutsu[id] {
+        return SafetyClass::Genbutsu;
+    }
+    if id >= 27 {
+        return SafetyClass::Honor {
+            visible: visible[id],
+        };
+    }
+
+    // the ryanmen shapes waiting on it, as the two tiles of each along with
+    // the other tile they wait on
+    let num = id % 9;
+    let shapes: Vec<([usize; 2], usize)> = [
+        (num <= 5).then(|| ([id + 1, id + 2], id + 3)),
+        (num >= 3).then(|| ([id - 2, id - 1], id - 3)),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if shapes.iter().all(|&(_, other)| genbutsu[other]) {
+        return SafetyClass::Suji;
+    }
+    let blocked = |n: u8| {
+        shapes
+            .iter()
+            .all(|&([a, b], _)| visible[a] >= n || visible[b] >= n)
+    };
+    if blocked(4) {
+        SafetyClass::NoChance
+    } else if blocked(3) {
+        SafetyClass::OneChance
+    } else {
+        SafetyClass::Unsafe
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use convlog::t;
+
+    #[test]
+    fn classes() {
+        let mut genbutsu = [false; 34];
+        let mut visible = [0; 34];
+        for t in [t!(4m), t!(1p), t!(7p), t!(E)] {
+            genbutsu[t.as_usize()] = true;
+        }
+        visible[t!(2s).as_usize()] = 4;
+        visible[t!(8s).as_usize()] = 3;
+        visible[t!(S).as_usize()] = 2;
+
+        let class = |t: Tile| classify(t, &genbutsu, &visible);
+        assert_eq!(class(t!(E)), SafetyClass::Genbutsu);
+        assert_eq!(class(t!(1m)), SafetyClass::Suji);
+        assert_eq!(class(t!(7m)), SafetyClass::Suji);
+        assert_eq!(class(t!(5m)), SafetyClass::Unsafe);
+        assert_eq!(class(t!(4p)), SafetyClass::Suji);
+        assert_eq!(class(t!(1s)), SafetyClass::NoChance);
+        assert_eq!(class(t!(3s)), SafetyClass::Unsafe);
+        assert_eq!(class(t!(9s)), SafetyClass::OneChance);
+        assert_eq!(class(t!(S)), SafetyClass::Honor { visible: 2 });
+        assert!(class(t!(S)).rank() < class(t!(9s)).rank());
+    }
+}
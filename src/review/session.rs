@@ -0,0 +1,79 @@
+use super::process::{EngineLog, EngineProcess, Watchdog};
+use super::transcript::{Replay, Transcript};
+use std::process::Command;
+
+use anyhow::Result;
+
+/// The engine side of a review, either a live engine process or a recorded
+/// transcript played back.
+pub enum Session {
+    Live {
+        process: Box<EngineProcess>,
+        transcript: Transcript,
+    },
+    Replay(Replay),
+}
+
+impl Session {
+    pub fn spawn(command: Command, watchdog: Watchdog, verbose: bool) -> Result<Self> {
+        let process = Box::new(EngineProcess::spawn(command, watchdog, verbose)?);
+        Ok(Self::Live {
+            process,
+            transcript: Transcript::default(),
+        })
+    }
+
+    #[inline]
+    pub const fn replay(transcript: Transcript) -> Self {
+        Self::Replay(Replay::new(transcript))
+    }
+
+    pub fn send(&mut self, line: &str) -> Result<()> {
+        match self {
+            Self::Live {
+                process,
+                transcript,
+            } => {
+                transcript.record_sent(line);
+                process.send(line)
+            }
+            Self::Replay(replay) => replay.send(line),
+        }
+    }
+
+    pub fn recv(&mut self) -> Result<String> {
+        match self {
+            Self::Live {
+                process,
+                transcript,
+            } => {
+                let line = process.recv()?;
+                transcript.record_received(&line);
+                Ok(line)
+            }
+            Self::Replay(replay) => replay.recv(),
+        }
+    }
+
+    /// Tells the engine there is nothing more to send.
+    pub fn close_stdin(&mut self) {
+        if let Self::Live { process, .. } = self {
+            process.close_stdin();
+        }
+    }
+
+    /// Waits for the engine to exit and returns everything exchanged with it,
+    /// along with what it wrote to stderr, which is empty for a replay.
+    pub fn finish(self) -> Result<(Transcript, EngineLog)> {
+        match self {
+            Self::Live {
+                process,
+                transcript,
+            } => {
+                let log = process.finish()?;
+                Ok((transcript, log))
+            }
+            Self::Replay(replay) => Ok((replay.finish()?, EngineLog::default())),
+        }
+    }
+}
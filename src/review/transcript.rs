@@ -0,0 +1,165 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::io::prelude::*;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail, ensure};
+
+const SENT_PREFIX: &str = "> ";
+const RECEIVED_PREFIX: &str = "< ";
+
+/// Every line written to and read from an engine during a review, in order.
+///
+/// On disk it is a text file with one line per entry, prefixed with "> " for
+/// lines sent to the engine and "< " for lines received from it, the same as
+/// the verbose logs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Transcript {
+    lines: Vec<Line>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Line {
+    Sent(String),
+    Received(String),
+}
+
+impl Transcript {
+    #[inline]
+    pub fn record_sent(&mut self, line: &str) {
+        self.lines.push(Line::Sent(line.to_owned()));
+    }
+
+    #[inline]
+    pub fn record_received(&mut self, line: &str) {
+        self.lines.push(Line::Received(line.to_owned()));
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("failed to open transcript {}", path.display()))?;
+        Self::from_reader(BufReader::new(file))
+            .with_context(|| format!("failed to read transcript {}", path.display()))
+    }
+
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
+        let mut lines = vec![];
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let entry = if let Some(l) = line.strip_prefix(SENT_PREFIX) {
+                Line::Sent(l.to_owned())
+            } else if let Some(l) = line.strip_prefix(RECEIVED_PREFIX) {
+                Line::Received(l.to_owned())
+            } else {
+                bail!("invalid transcript line {}: {line:?}", i + 1);
+            };
+            lines.push(entry);
+        }
+        Ok(Self { lines })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut file = File::create(path)
+            .with_context(|| format!("failed to create transcript {}", path.display()))?;
+        file.write_all(self.to_string().as_bytes())
+            .with_context(|| format!("failed to write transcript {}", path.display()))
+    }
+}
+
+impl std::fmt::Display for Transcript {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in &self.lines {
+            match line {
+                Line::Sent(l) => writeln!(f, "{SENT_PREFIX}{l}")?,
+                Line::Received(l) => writeln!(f, "{RECEIVED_PREFIX}{l}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Plays the engine's part of a transcript back, checking that what is sent
+/// matches what was recorded.
+pub struct Replay {
+    transcript: Transcript,
+    cursor: usize,
+}
+
+impl Replay {
+    #[inline]
+    pub const fn new(transcript: Transcript) -> Self {
+        Self {
+            transcript,
+            cursor: 0,
+        }
+    }
+
+    pub fn send(&mut self, line: &str) -> Result<()> {
+        match self.transcript.lines.get(self.cursor) {
+            Some(Line::Sent(l)) if l == line => {
+                self.cursor += 1;
+                Ok(())
+            }
+            Some(Line::Sent(l)) => bail!(
+                "transcript mismatch at line {}: expected to send {l:?}, got {line:?}",
+                self.cursor + 1,
+            ),
+            Some(Line::Received(_)) => bail!(
+                "transcript mismatch at line {}: expected to receive, got a line to send",
+                self.cursor + 1,
+            ),
+            None => bail!("transcript ended before {line:?} was sent"),
+        }
+    }
+
+    pub fn recv(&mut self) -> Result<String> {
+        match self.transcript.lines.get(self.cursor) {
+            Some(Line::Received(l)) => {
+                self.cursor += 1;
+                Ok(l.clone())
+            }
+            Some(Line::Sent(l)) => bail!(
+                "transcript mismatch at line {}: expected to send {l:?}, got a read",
+                self.cursor + 1,
+            ),
+            None => bail!("transcript ended unexpectedly"),
+        }
+    }
+
+    pub fn finish(self) -> Result<Transcript> {
+        ensure!(
+            self.cursor == self.transcript.lines.len(),
+            "transcript has {} lines left over",
+            self.transcript.lines.len() - self.cursor,
+        );
+        Ok(self.transcript)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip_and_replay() {
+        let mut transcript = Transcript::default();
+        transcript.record_sent(r#"{"type":"start_game"}"#);
+        transcript.record_received(r#"{"type":"none"}"#);
+        transcript.record_sent(r#"{"type":"end_game"}"#);
+
+        let text = transcript.to_string();
+        let parsed = Transcript::from_reader(text.as_bytes()).unwrap();
+        assert_eq!(parsed, transcript);
+
+        let mut replay = Replay::new(parsed);
+        replay.send(r#"{"type":"start_game"}"#).unwrap();
+        assert_eq!(replay.recv().unwrap(), r#"{"type":"none"}"#);
+        replay.recv().unwrap_err();
+        replay.send(r#"{"type":"end_kyoku"}"#).unwrap_err();
+        replay.send(r#"{"type":"end_game"}"#).unwrap();
+        replay.finish().unwrap();
+    }
+}
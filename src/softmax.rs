@@ -9,7 +9,9 @@ pub fn softmax(arr: &mut [f32], temperature: f32) {
     }
 
     if !eq(temperature, 1.) {
-        arr.iter_mut().for_each(|x| *x /= temperature);
+        for x in arr.iter_mut() {
+            *x /= temperature;
+        }
     }
 
     let max = arr
@@ -20,8 +22,9 @@ pub fn softmax(arr: &mut [f32], temperature: f32) {
     let sum: f32 = arr.iter().copied().map(|x| (x - max).exp()).sum();
 
     let offset = max + sum.ln();
-    arr.iter_mut()
-        .for_each(|x| *x = (*x - offset).exp().clamp(0., 1.));
+    for x in arr.iter_mut() {
+        *x = (*x - offset).exp().clamp(0., 1.);
+    }
 }
 
 #[cfg(test)]
@@ -1,15 +1,17 @@
 use crate::tehai::Tehai;
 
-use anyhow::{Context, Result};
-use convlog::{Event, Tile, tile_set_eq};
-use serde::Serialize;
+use anyhow::Result;
+use convlog::{Board, Discard, Event, Fuuro, Tile};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Default, Serialize)]
+/// The hand of the player to review, along with what everyone at the table
+/// can see.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct State {
     #[serde(skip)]
     actor: u8,
     tehai: Tehai,
-    fuuros: Vec<Fuuro>,
+    board: Board,
 }
 
 impl State {
@@ -21,29 +23,16 @@ impl State {
         }
     }
 
-    /// Argument `event` must be one of
-    ///
-    /// * StartKyoku
-    /// * Tsumo
-    /// * Dahai
-    /// * Chi
-    /// * Pon
-    /// * Kakan
-    /// * Daiminkan
-    /// * Ankan
-    ///
-    /// and the `actor` must be the target actor.
+    /// Updates the hand for the events of the player, and the board for
+    /// everything. Events not affecting either are no-op.
     ///
-    /// Otherwise this is a no-op.
+    /// Only the hand of the player is tracked, in `tehai`. The board is given
+    /// the events with every hand and draw masked, so that nothing hidden from
+    /// the player ends up in it.
     pub fn update(&mut self, event: &Event) -> Result<()> {
         match *event {
-            Event::StartKyoku { tehais, .. } => {
-                self.tehai.haipai(&tehais[self.actor as usize]);
-                self.fuuros.clear();
-            }
-
+            Event::StartKyoku { tehais, .. } => self.tehai.haipai(&tehais[self.actor as usize]),
             Event::Tsumo { actor, pai } if actor == self.actor => self.tehai.tsumo(pai),
-
             Event::Dahai {
                 actor,
                 pai,
@@ -55,148 +44,157 @@ impl State {
                     self.tehai.tedashi(pai);
                 }
             }
-
             Event::Chi {
-                actor,
-                target,
-                pai,
-                consumed,
-            } if actor == self.actor => {
-                self.tehai.remove_multiple(&consumed);
-
-                let fuuro = Fuuro::Chi {
-                    target,
-                    pai,
-                    consumed,
-                };
-                self.fuuros.push(fuuro);
+                actor, consumed, ..
             }
-
-            Event::Pon {
-                actor,
-                target,
-                pai,
-                consumed,
-            } if actor == self.actor => {
-                self.tehai.remove_multiple(&consumed);
-
-                let fuuro = Fuuro::Pon {
-                    target,
-                    pai,
-                    consumed,
-                };
-                self.fuuros.push(fuuro);
-            }
-
+            | Event::Pon {
+                actor, consumed, ..
+            } if actor == self.actor => self.tehai.remove_multiple(&consumed),
             Event::Daiminkan {
-                actor,
-                target,
-                pai,
-                consumed,
-            } if actor == self.actor => {
+                actor, consumed, ..
+            } if actor == self.actor => self.tehai.remove_multiple(&consumed),
+            Event::Kakan { actor, pai, .. } if actor == self.actor => self.tehai.tedashi(pai),
+            Event::Ankan { actor, consumed } if actor == self.actor => {
                 self.tehai.remove_multiple(&consumed);
-
-                let fuuro = Fuuro::Daiminkan {
-                    target,
-                    pai,
-                    consumed,
-                };
-                self.fuuros.push(fuuro);
             }
-
-            Event::Kakan {
-                actor,
-                pai,
-                consumed,
-            } if actor == self.actor => {
-                self.tehai.tedashi(pai);
-
-                let (
-                    previous_pon_idx,
-                    previous_pon_target,
-                    previous_pon_pai,
-                    previous_pon_consumed,
-                ) = self
-                    .fuuros
-                    .iter()
-                    .enumerate()
-                    .find_map(|(idx, f)| match *f {
-                        Fuuro::Pon {
-                            target: pon_target,
-                            pai: pon_pai,
-                            consumed: pon_consumed,
-                        } if tile_set_eq(
-                            &[pon_pai, pon_consumed[0], pon_consumed[1]],
-                            &consumed,
-                            false,
-                        ) =>
-                        {
-                            Some((idx, pon_target, pon_pai, pon_consumed))
-                        }
-
-                        _ => None,
-                    })
-                    .context("invalid state: previous Pon not found for Kakan")?;
-
-                let fuuro = Fuuro::Kakan {
-                    pai,
-                    previous_pon_target,
-                    previous_pon_pai,
-                    consumed: previous_pon_consumed,
-                };
-                self.fuuros[previous_pon_idx] = fuuro;
+            _ => (),
+        }
+        match *event {
+            Event::StartKyoku { .. } | Event::Tsumo { .. } => {
+                self.board.apply(&mask(event.clone()))?;
             }
+            _ => self.board.apply(event)?,
+        }
+        Ok(())
+    }
 
-            Event::Ankan { actor, consumed } if actor == self.actor => {
-                self.tehai.remove_multiple(&consumed);
-
-                let fuuro = Fuuro::Ankan { consumed };
-                self.fuuros.push(fuuro);
+    /// Tiles in the hand of the same kind as `tile`, aka ones first.
+    pub fn tiles_like(&self, tile: Tile) -> Vec<Tile> {
+        let mut tiles = vec![];
+        for t in [tile.deaka().akaize(), tile.deaka()] {
+            for _ in 0..self.tehai.count(t) {
+                tiles.push(t);
             }
+            if t == tile.deaka() {
+                break;
+            }
+        }
+        tiles
+    }
 
-            _ => (),
-        };
+    /// Tiles of the Pon of the same kind as `tile`, which are the called tile
+    /// followed by the consumed ones, as in `consumed` of Kakan.
+    pub fn pon_of(&self, tile: Tile) -> Option<[Tile; 3]> {
+        self.board.seats[self.actor as usize]
+            .fuuros
+            .iter()
+            .find_map(|f| match *f {
+                Fuuro::Pon { pai, consumed, .. } if pai.deaka() == tile.deaka() => {
+                    Some([pai, consumed[0], consumed[1]])
+                }
+                _ => None,
+            })
+    }
 
-        Ok(())
+    #[inline]
+    pub const fn tehai(&self) -> &Tehai {
+        &self.tehai
     }
 
     #[inline]
-    pub fn has_tile(&self, tile: Tile) -> bool {
-        self.tehai.view().contains(&tile)
+    pub const fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Number of each kind of tile the player can see, in the hand, all
+    /// rivers and melds, and the dora indicators.
+    pub fn visible_counts(&self) -> [u8; 34] {
+        let mut counts = self.board.visible_counts();
+        for (count, n) in counts.iter_mut().zip(self.tehai.counts34()) {
+            *count += n;
+        }
+        counts
     }
 
     #[inline]
     pub const fn player_id(&self) -> u8 {
         self.actor
     }
+
+    /// What an entry of a review keeps to draw the table with.
+    pub fn snapshot(&self) -> Snapshot {
+        let Board {
+            bakaze,
+            kyoku,
+            honba,
+            kyotaku,
+            oya,
+            scores,
+            ref dora_markers,
+            tiles_left,
+            ref seats,
+        } = self.board;
+        Snapshot {
+            tehai: self.tehai,
+            board: Table {
+                bakaze,
+                kyoku,
+                honba,
+                kyotaku,
+                oya,
+                scores,
+                dora_markers: dora_markers.clone(),
+                tiles_left,
+                seats: seats.each_ref().map(|s| TableSeat {
+                    river: s.river.clone(),
+                    fuuros: s.fuuros.clone(),
+                    riichi_at: s.riichi_at,
+                }),
+            },
+        }
+    }
+}
+
+/// The part of a [`State`] drawn in the report, without the hidden hands and
+/// the rest of the bookkeeping of [`Board`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    tehai: Tehai,
+    board: Table,
+}
+
+/// The fields of the same names in [`Board`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Table {
+    bakaze: Option<Tile>,
+    kyoku: u8,
+    honba: u8,
+    kyotaku: u8,
+    oya: u8,
+    scores: [i32; 4],
+    dora_markers: Vec<Tile>,
+    tiles_left: u8,
+    seats: [TableSeat; 4],
+}
+
+/// The fields of the same names in [`convlog::Seat`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableSeat {
+    river: Vec<Discard>,
+    fuuros: Vec<Fuuro>,
+    riichi_at: Option<usize>,
 }
 
-#[derive(Debug, Clone, Copy, Serialize)]
-#[serde(tag = "type")]
-#[serde(rename_all = "snake_case")]
-pub enum Fuuro {
-    Chi {
-        target: u8,
-        pai: Tile,
-        consumed: [Tile; 2],
-    },
-    Pon {
-        target: u8,
-        pai: Tile,
-        consumed: [Tile; 2],
-    },
-    Daiminkan {
-        target: u8,
-        pai: Tile,
-        consumed: [Tile; 3],
-    },
-    Kakan {
-        pai: Tile,
-        previous_pon_target: u8,
-        previous_pon_pai: Tile,
-        consumed: [Tile; 2],
-    },
-    Ankan {
-        consumed: [Tile; 4],
-    },
+fn mask(mut event: Event) -> Event {
+    match &mut event {
+        Event::StartKyoku { tehais, .. } => {
+            tehais
+                .iter_mut()
+                .flatten()
+                .for_each(|t| *t = Tile::default());
+        }
+        Event::Tsumo { pai, .. } => *pai = Tile::default(),
+        _ => (),
+    }
+    event
 }
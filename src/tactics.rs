@@ -1,17 +1,267 @@
-use std::collections::HashMap;
+use crate::opts::{TacticsAction, TacticsPreset};
+use std::fs;
+use std::io::prelude::*;
+use std::path::Path;
 
+use anyhow::{Context, Result, anyhow, ensure};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{self as json, Value};
+use serde_with::skip_serializing_none;
+use tempfile::NamedTempFile;
 
-#[derive(Serialize, Deserialize)]
+/// The bound akochan puts on every element of `jun_pt`.
+const JUN_PT_BOUND: i32 = 200;
+
+/// A tactics file of akochan.
+#[derive(Debug)]
 pub struct TacticsJson {
     pub tactics: Tactics,
+
+    /// Anything next to `tactics`, which akochan does not read.
+    pub other_fields: json::Map<String, Value>,
 }
 
-#[derive(Serialize, Deserialize)]
+/// The fields known to akochan's `pipe_detailed`. All but `jun_pt` fall back
+/// to akochan's defaults if absent.
+#[skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Tactics {
     pub jun_pt: [i32; 4],
+    pub base: Option<Base>,
+
+    pub do_kan_ordinary: Option<bool>,
+    pub do_kakan_inclusive: Option<bool>,
+    /// Probabilities of gaining 0 to 13 han by a kan.
+    pub han_shift_prob_kan: Option<[f64; 14]>,
+    pub hanfu_weight_ron: Option<Vec<HanfuWeight>>,
+
+    pub agari_prob_est: Option<Estimator>,
+    pub houjuu_est: Option<Estimator>,
+    pub inclusive_fold_est: Option<Estimator>,
+    pub jun_est: Option<Estimator>,
+    pub katachi_est: Option<Estimator>,
+    pub mc_init: Option<Estimator>,
+    pub my_keiten_prob_est: Option<Estimator>,
+    pub other_end_prob_est: Option<Estimator>,
+    pub other_keiten_prob_est: Option<Estimator>,
+    pub result_other_est: Option<Estimator>,
+    pub ron_ratio_est: Option<Estimator>,
+    pub ryukyoku_prob_est: Option<Estimator>,
+    pub sbr_est: Option<Estimator>,
+    pub tas_est: Option<Estimator>,
+    pub tenpai_after_est: Option<Estimator>,
+    pub tenpai_prob_est: Option<Estimator>,
+    pub tsumo_num_est: Option<Estimator>,
+
+    pub other_end_est_begin: Option<u8>,
+    pub tenpai_after_est_begin: Option<u8>,
+    pub tenpai_after_use_other_reach: Option<bool>,
+    pub tsumo_num_ratio: Option<f64>,
+    pub use_agari_coeff_tp_an: Option<u8>,
+    pub use_agari_coeff_tp_fnm: Option<u8>,
+    pub use_other_end_ar: Option<bool>,
+    pub use_ori_exp_at_dp_fuuro: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Base {
+    Default,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Estimator {
+    Ako,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HanfuWeight {
+    pub han: u8,
+    pub fu: u8,
+    pub value: f64,
+}
+
+/// Changes to the tactics file from the command line, applied in the order of
+/// the fields.
+pub struct Overrides<'a> {
+    pub preset: Option<TacticsPreset>,
+    pub jun_pt: Option<[i32; 4]>,
+    pub fields: &'a [(String, Value)],
+}
+
+impl TacticsPreset {
+    pub const fn jun_pt(self) -> [i32; 4] {
+        match self {
+            // the 4th place of Tenhou loses 60 at 1-dan and 15 more per dan
+            Self::TenhouHouou => [90, 45, 0, -165],
+            Self::TenhouTokujou => [75, 30, 0, -150],
+            Self::MjsoulJade => [110, 55, 0, -165],
+            Self::MjsoulThrone => [120, 60, 0, -195],
+            Self::MLeague => [50, 10, -10, -30],
+        }
+    }
+}
+
+impl Overrides<'_> {
+    pub const fn is_empty(&self) -> bool {
+        self.preset.is_none() && self.jun_pt.is_none() && self.fields.is_empty()
+    }
+}
+
+impl Tactics {
+    /// Parses and validates the content of `tactics`. Errors name the field
+    /// at fault.
+    pub fn from_value(value: Value) -> Result<Self> {
+        let tactics: Self = serde_path_to_error::deserialize(value).map_err(|e| {
+            let path = e.path().to_string();
+            if path == "." {
+                anyhow!("{}", e.inner())
+            } else {
+                anyhow!("field `{path}`: {}", e.inner())
+            }
+        })?;
+        tactics.validate()?;
+        Ok(tactics)
+    }
+
+    /// Checks what the types cannot tell.
+    fn validate(&self) -> Result<()> {
+        for (i, &pt) in self.jun_pt.iter().enumerate() {
+            ensure!(
+                (-JUN_PT_BOUND..=JUN_PT_BOUND).contains(&pt),
+                "field `jun_pt[{i}]`: {pt} is not within [-{JUN_PT_BOUND}, {JUN_PT_BOUND}]",
+            );
+        }
+        if let Some(probs) = &self.han_shift_prob_kan {
+            for (i, &p) in probs.iter().enumerate() {
+                ensure!(
+                    (0. ..=1.).contains(&p),
+                    "field `han_shift_prob_kan[{i}]`: {p} is not a probability",
+                );
+            }
+        }
+        if let Some(weights) = &self.hanfu_weight_ron {
+            for (i, w) in weights.iter().enumerate() {
+                ensure!(
+                    w.value >= 0.,
+                    "field `hanfu_weight_ron[{i}].value`: {} is negative",
+                    w.value,
+                );
+            }
+        }
+        if let Some(ratio) = self.tsumo_num_ratio {
+            ensure!(
+                ratio > 0.,
+                "field `tsumo_num_ratio`: {ratio} is not greater than zero",
+            );
+        }
+        Ok(())
+    }
+}
+
+impl TacticsJson {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read tactics file {}", path.display()))?;
+        Self::from_str(&content).with_context(|| format!("invalid tactics file {}", path.display()))
+    }
+
+    fn from_str(content: &str) -> Result<Self> {
+        let mut other_fields: json::Map<_, _> = json::from_str(content)?;
+        let tactics = other_fields
+            .remove("tactics")
+            .context("missing field `tactics`")?;
+        Ok(Self {
+            tactics: Tactics::from_value(tactics)?,
+            other_fields,
+        })
+    }
+
+    pub fn apply(&mut self, overrides: &Overrides<'_>) -> Result<()> {
+        if let Some(preset) = overrides.preset {
+            self.tactics.jun_pt = preset.jun_pt();
+        }
+        if let Some(jun_pt) = overrides.jun_pt {
+            self.tactics.jun_pt = jun_pt;
+        }
+        let Value::Object(mut tactics) = json::to_value(&self.tactics)? else {
+            unreachable!();
+        };
+        for (key, value) in overrides.fields {
+            tactics.insert(key.clone(), value.clone());
+        }
+        self.tactics = Tactics::from_value(Value::Object(tactics))
+            .context("invalid tactics after overrides")?;
+        Ok(())
+    }
+
+    /// Writes it to a temporary file, which is deleted when dropped.
+    pub fn write_temp(&self) -> Result<NamedTempFile> {
+        let mut file = tempfile::Builder::new()
+            .prefix("tactics-")
+            .suffix(".json")
+            .tempfile()
+            .context("failed to create temporary tactics file")?;
+        let mut root = self.other_fields.clone();
+        root.insert("tactics".to_owned(), json::to_value(&self.tactics)?);
+        json::to_writer_pretty(&mut file, &root).context("failed to write tactics")?;
+        file.flush().context("failed to write tactics")?;
+        Ok(file)
+    }
+}
+
+pub fn run(action: TacticsAction) -> Result<()> {
+    match action {
+        TacticsAction::Check { file } => {
+            TacticsJson::load(&file)?;
+            println!("{}: ok", file.display());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn check() {
+        let tactics = TacticsJson::from_str(include_str!("../tactics.json")).unwrap();
+        assert_eq!(tactics.tactics.jun_pt, [90, 45, 0, -135]);
+
+        let err = |content: &str| format!("{:#}", TacticsJson::from_str(content).unwrap_err());
+        assert!(err(r#"{"tactics":{"jun_pt":[1,2,3,4],"jun_estt":"ako"}}"#).contains("jun_estt"));
+        assert!(err(r#"{"tactics":{"jun_pt":[1,2,3,4],"jun_est":"akoo"}}"#).contains("`jun_est`"));
+        assert!(err(r#"{"tactics":{"jun_pt":[1,2,3]}}"#).contains("`jun_pt`"));
+        assert!(err(r#"{"tactics":{"jun_pt":[1,2,3,-400]}}"#).contains("`jun_pt[3]`"));
+        assert!(
+            err(r#"{"tactics":{"jun_pt":[1,2,3,4],"han_shift_prob_kan":[1]}}"#)
+                .contains("`han_shift_prob_kan`")
+        );
+        assert!(err(r#"{"jun_pt":[1,2,3,4]}"#).contains("`tactics`"));
+
+        let mut tactics =
+            TacticsJson::from_str(r#"{"tactics":{"jun_pt":[1,2,3,4]},"fake":{}}"#).unwrap();
+        let fields = [("do_kan_ordinary".to_owned(), Value::Bool(true))];
+        let overrides = Overrides {
+            preset: Some(TacticsPreset::MLeague),
+            jun_pt: None,
+            fields: &fields,
+        };
+        tactics.apply(&overrides).unwrap();
+        assert_eq!(tactics.tactics.jun_pt, [50, 10, -10, -30]);
+        assert_eq!(tactics.tactics.do_kan_ordinary, Some(true));
+        assert!(tactics.other_fields.contains_key("fake"));
 
-    #[serde(flatten)]
-    pub other_fields: HashMap<String, Value>,
+        let fields = [("do_kan_ordinary".to_owned(), Value::from(1))];
+        let overrides = Overrides {
+            preset: None,
+            jun_pt: None,
+            fields: &fields,
+        };
+        assert!(tactics.apply(&overrides).is_err());
+    }
 }
@@ -1,98 +1,205 @@
-use convlog::Tile;
+use convlog::{Tile, must_tile};
 
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(Debug, Clone, Default)]
-pub struct Tehai {
-    inner: Vec<Tile>,
-    is_sorted: bool,
-}
+/// 34 kinds of tiles followed by the 3 aka ones.
+const SLOTS: usize = 37;
 
-impl From<Vec<Tile>> for Tehai {
-    #[inline]
-    fn from(tehai: Vec<Tile>) -> Self {
-        let mut ret = Self {
-            inner: tehai,
-            is_sorted: false,
-        };
-        ret.sort();
-        ret
-    }
+/// A hand as counts of each tile, with the drawn tile kept apart until the
+/// next discard.
+#[derive(Debug, Clone, Copy)]
+pub struct Tehai {
+    counts: [u8; SLOTS],
+    drawn: Option<Tile>,
 }
 
-impl From<Tehai> for Vec<Tile> {
-    #[inline]
-    fn from(tehai: Tehai) -> Self {
-        tehai.inner
+impl Default for Tehai {
+    fn default() -> Self {
+        Self {
+            counts: [0; SLOTS],
+            drawn: None,
+        }
     }
 }
 
+/// Serialized as a list of tiles, sorted with aka ones before the normal ones
+/// of the same number, and the drawn tile, if any, at last.
 impl Serialize for Tehai {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        self.inner.serialize(serializer)
+        serializer.collect_seq(self.iter())
+    }
+}
+
+/// The last tile of a hand of 3n+2 tiles is taken as the drawn one, which
+/// serializes back to the same list even if it was not.
+impl<'de> Deserialize<'de> for Tehai {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut tiles = Vec::<Tile>::deserialize(deserializer)?;
+        let drawn = if tiles.len() % 3 == 2 {
+            tiles.pop()
+        } else {
+            None
+        };
+        let mut tehai = Self::default();
+        tehai.haipai(&tiles);
+        tehai.drawn = drawn;
+        Ok(tehai)
     }
 }
 
 impl Tehai {
     /// Resets current tehai.
-    #[inline]
     pub fn haipai(&mut self, tiles: &[Tile]) {
-        self.inner = tiles.to_vec();
-        self.sort();
+        *self = Self::default();
+        for &tile in tiles {
+            self.add(tile);
+        }
     }
 
     /// Tsumo a tile.
     #[inline]
     pub fn tsumo(&mut self, tile: Tile) {
-        self.inner.push(tile);
-        self.is_sorted = false;
+        self.merge_drawn();
+        self.drawn = Some(tile);
     }
 
     /// Tsumogiri a tile.
     #[inline]
-    pub fn tsumogiri(&mut self) {
-        self.inner.pop();
-        self.is_sorted = true;
+    pub const fn tsumogiri(&mut self) {
+        self.drawn = None;
     }
 
     /// Tedashi a tile.
+    #[inline]
     pub fn tedashi(&mut self, tile: Tile) {
-        if !self.is_sorted {
-            self.sort();
+        self.merge_drawn();
+        self.remove(tile);
+    }
+
+    /// Remove several tiles for fuuro.
+    pub fn remove_multiple(&mut self, tiles: &[Tile]) {
+        self.merge_drawn();
+        for &tile in tiles {
+            self.remove(tile);
         }
+    }
+
+    /// Number of the tile in hand, aka ones counted apart.
+    #[inline]
+    pub fn count(&self, tile: Tile) -> u8 {
+        let drawn = u8::from(self.drawn == Some(tile));
+        self.counts
+            .get(tile.as_usize())
+            .copied()
+            .unwrap_or_default()
+            + drawn
+    }
 
-        if let Ok(idx) = self.inner.binary_search(&tile) {
-            self.inner.remove(idx);
+    /// Counts of the 34 kinds of tiles, with aka ones counted as normal, as
+    /// taken by shanten calculations.
+    pub fn counts34(&self) -> [u8; 34] {
+        let mut counts = [0; 34];
+        counts.copy_from_slice(&self.counts[..34]);
+        for (aka, normal) in [(34, 4), (35, 13), (36, 22)] {
+            counts[normal] += self.counts[aka];
+        }
+        if let Some(tile) = self.drawn {
+            counts[tile.deaka().as_usize()] += 1;
         }
+        counts
     }
 
-    /// Remove several tiles for fuuro.
-    pub fn remove_multiple(&mut self, tiles: &[Tile]) {
-        // usually, it is already sorted, except for kakan and ankan.
-        if !self.is_sorted {
-            self.sort();
+    /// Tiles in hand in the order they are serialized.
+    pub fn iter(&self) -> impl Iterator<Item = Tile> + '_ {
+        (0..34)
+            .flat_map(|id| {
+                let tile = must_tile!(id);
+                let aka = if tile.akaize() == tile {
+                    0
+                } else {
+                    self.counts[tile.akaize().as_usize()]
+                };
+                let normal = self.counts[id];
+                (0..aka)
+                    .map(move |_| tile.akaize())
+                    .chain((0..normal).map(move |_| tile))
+            })
+            .chain(self.drawn)
+    }
+
+    fn add(&mut self, tile: Tile) {
+        if let Some(count) = self.counts.get_mut(tile.as_usize()) {
+            *count += 1;
         }
+    }
 
-        for &tile in tiles {
-            if let Ok(idx) = self.inner.binary_search(&tile) {
-                self.inner.remove(idx);
-            }
+    fn remove(&mut self, tile: Tile) {
+        if let Some(count) = self.counts.get_mut(tile.as_usize()) {
+            *count = count.saturating_sub(1);
         }
     }
 
-    /// Sort the tiles. Aka tile will be before normal tile of the same
-    /// sequence.
-    #[inline]
-    fn sort(&mut self) {
-        self.inner.sort_unstable();
-        self.is_sorted = true;
+    fn merge_drawn(&mut self) {
+        if let Some(tile) = self.drawn.take() {
+            self.add(tile);
+        }
     }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use convlog::t;
+    use serde_json as json;
+
+    #[test]
+    fn serialize() {
+        let mut tehai = Tehai::default();
+        tehai.haipai(&[
+            t!(E),
+            t!(5m),
+            t!(1m),
+            t!(5mr),
+            t!(9s),
+            t!(5s),
+            t!(5m),
+            t!(C),
+            t!(2p),
+            t!(3p),
+            t!(4p),
+            t!(7s),
+            t!(8s),
+        ]);
+        tehai.tsumo(t!(1m));
+        let s = json::to_string(&tehai).unwrap();
+        assert_eq!(
+            s,
+            r#"["1m","5mr","5m","5m","2p","3p","4p","5s","7s","8s","9s","E","C","1m"]"#,
+        );
+        assert_eq!(tehai.count(t!(1m)), 2);
+        assert_eq!(tehai.counts34()[t!(5m).as_usize()], 3);
+
+        tehai.tedashi(t!(5mr));
+        tehai.tsumo(t!(5sr));
+        tehai.tsumogiri();
+        tehai.remove_multiple(&[t!(2p), t!(4p)]);
+        let s = json::to_string(&tehai).unwrap();
+        assert_eq!(
+            s,
+            r#"["1m","1m","5m","5m","3p","5s","7s","8s","9s","E","C"]"#
+        );
 
-    /// Returns a view of current tehai.
-    pub fn view(&self) -> &[Tile] {
-        &self.inner
+        tehai.tedashi(t!(9s));
+        tehai.tsumo(t!(2m));
+        let s = json::to_string(&tehai).unwrap();
+        let read: Tehai = json::from_str(&s).unwrap();
+        assert_eq!(json::to_string(&read).unwrap(), s);
+        assert_eq!(read.drawn, Some(t!(2m)));
     }
 }
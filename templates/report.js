@@ -55,9 +55,159 @@ function scrollHandler() {
   }
 }
 
+// open the entry linked from the top mistakes
+function openLinkedEntry() {
+  const id = decodeURIComponent(location.hash.slice(1));
+  const target = id && document.getElementById(id);
+  if (target instanceof HTMLDetailsElement) {
+    target.open = true;
+  }
+}
+
+// the built-in replay viewer, drawing the boards computed for each event of a
+// kyoku, which are parsed from the embedded JSON on first use
+const SVG_NS = 'http://www.w3.org/2000/svg';
+const replays = new Map();
+
+function tileItem(tile, className) {
+  const svg = document.createElementNS(SVG_NS, 'svg');
+  svg.setAttribute('class', 'tile');
+  const use = document.createElementNS(SVG_NS, 'use');
+  if (tile === '?' || tile === 'back') {
+    use.setAttribute('class', 'back');
+    use.setAttribute('href', '#tile');
+  } else {
+    use.setAttribute('class', 'face');
+    use.setAttribute('href', `#pai-${tile.toLowerCase()}`);
+  }
+  svg.appendChild(use);
+  const li = document.createElement('li');
+  if (className) {
+    li.className = className;
+  }
+  li.appendChild(svg);
+  return li;
+}
+
+// the tiles of a meld, with the called one rotated to the side of the target
+function fuuroItems(fuuro, owner) {
+  if (fuuro.type === 'ankan') {
+    const [, a, b] = fuuro.consumed;
+    return [tileItem('back'), tileItem(a), tileItem(b), tileItem('back')];
+  }
+  const target = fuuro.type === 'kakan' ? fuuro.previous_pon_target : fuuro.target;
+  const called = fuuro.type === 'kakan' ? fuuro.previous_pon_pai : fuuro.pai;
+  const items = fuuro.consumed.map((t) => tileItem(t));
+  const rel = (target + 4 - owner) % 4;
+  const at = rel === 3 ? 0 : rel === 2 ? 1 : items.length;
+  items.splice(at, 0, tileItem(called, 'rotated'));
+  if (fuuro.type === 'kakan') {
+    items.splice(at + 1, 0, tileItem(fuuro.pai, 'rotated added'));
+  }
+  return items;
+}
+
+function describeEvent(event) {
+  const parts = [event.type];
+  if ('actor' in event) {
+    parts.push(`by ${event.actor}`);
+  }
+  if ('pai' in event) {
+    parts.push(event.pai);
+  }
+  if ('consumed' in event) {
+    parts.push(`(${event.consumed.join(' ')})`);
+  }
+  return parts.join(' ');
+}
+
+function getReplay(replayer) {
+  let replay = replays.get(replayer);
+  if (!replay) {
+    replay = JSON.parse(replayer.querySelector('script').textContent);
+    replay.pos = 0;
+    replays.set(replayer, replay);
+    replayer.querySelector('.replayer-pos').max = replay.boards.length - 1;
+  }
+  return replay;
+}
+
+function seekReplayer(replayer, pos) {
+  const replay = getReplay(replayer);
+  pos = Math.max(0, Math.min(pos, replay.boards.length - 1));
+  replay.pos = pos;
+  const board = replay.boards[pos];
+
+  replayer.querySelector('.replayer-pos').value = pos;
+  replayer.querySelector('.replayer-event').textContent =
+    `#${replay.first + pos} ${describeEvent(replay.events[pos])}`;
+  replayer.querySelector('.replayer-info').textContent =
+    `${board.bakaze}${board.kyoku}-${board.honba}, kyotaku ${board.kyotaku}, ${board.tiles_left} tiles left`;
+  replayer.querySelector('.dora-markers').replaceChildren(...board.dora_markers.map((t) => tileItem(t)));
+
+  for (const el of replayer.querySelectorAll('[data-seat]')) {
+    const i = Number(el.dataset.seat);
+    const seat = board.seats[i];
+    const riichi = seat.riichi_at != null ? ' riichi' : '';
+    el.querySelector('.replayer-score').textContent = `${board.scores[i]}${riichi}`;
+    el.querySelector('.river').replaceChildren(...seat.river.map((d, idx) => {
+      const classes = [];
+      if (d.tsumogiri) classes.push('tsumogiri');
+      if (d.called) classes.push('called');
+      if (idx === seat.riichi_at) classes.push('riichi');
+      return tileItem(d.pai, classes.join(' '));
+    }));
+    const hand = (seat.tehai ?? []).map((t) => tileItem(t));
+    if (seat.tsumo) {
+      hand.push(tileItem(seat.tsumo, 'tsumo'));
+    }
+    const fuuros = seat.fuuros.slice().reverse().map((f) => {
+      const ul = document.createElement('ul');
+      ul.className = 'consumed';
+      ul.replaceChildren(...fuuroItems(f, i));
+      const li = document.createElement('li');
+      li.className = 'fuuro';
+      li.appendChild(ul);
+      return li;
+    });
+    el.querySelector('.tehai-state').replaceChildren(...hand, ...fuuros);
+  }
+}
+
+function setupReplayer(replayer) {
+  const step = (delta) => seekReplayer(replayer, getReplay(replayer).pos + delta);
+  replayer.querySelector('.replayer-prev').addEventListener('click', () => step(-1));
+  replayer.querySelector('.replayer-next').addEventListener('click', () => step(1));
+  replayer.querySelector('.replayer-pos').addEventListener('input', (e) => {
+    seekReplayer(replayer, Number(e.target.value));
+  });
+  const details = replayer.closest('details');
+  details.addEventListener('toggle', () => {
+    if (details.open && !replays.has(replayer)) {
+      seekReplayer(replayer, 0);
+    }
+  });
+}
+
+// shows the board of a review entry, given the index of the last event before
+// the decision
+function jumpToEntry(kyoku, eventIndex) {
+  const replayer = document.querySelector(`.replayer[data-kyoku="${kyoku}"]`);
+  const replay = getReplay(replayer);
+  replayer.closest('details').open = true;
+  seekReplayer(replayer, eventIndex - replay.first);
+  replayer.scrollIntoView({ block: 'nearest' });
+}
+
 document.addEventListener('scroll', scrollHandler);
+window.addEventListener('hashchange', openLinkedEntry);
 
 document.addEventListener('DOMContentLoaded', () => {
   scrollHandler();
-  document.querySelectorAll('.latex').forEach(renderMathInElement);
+  openLinkedEntry();
+  document.querySelectorAll('.replayer').forEach(setupReplayer);
+  // KaTeX is not loaded in offline reports, where math is in MathML instead
+  if (typeof renderMathInElement === 'function') {
+    document.querySelectorAll('.latex').forEach(renderMathInElement);
+  }
 });
@@ -0,0 +1,1126 @@
+//! End-to-end tests running the whole review pipeline against fake-engine.
+
+#[path = "../convlog/tests/testdata/mod.rs"]
+mod testdata;
+
+use convlog::tenhou::{GameLength, Log};
+use serde_json::{self as json, Value};
+use std::fs;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::OnceLock;
+use tempfile::TempDir;
+use testdata::{TESTDATA, TestCase};
+
+/// Builds fake-engine, which is another package of the workspace, and returns
+/// the path to its executable.
+fn fake_engine() -> &'static Path {
+    static PATH: OnceLock<PathBuf> = OnceLock::new();
+    PATH.get_or_init(|| {
+        let output = Command::new(env!("CARGO"))
+            .args(["build", "-p", "fake-engine", "--message-format=json"])
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .stderr(Stdio::inherit())
+            .output()
+            .expect("failed to run cargo");
+        assert!(output.status.success(), "failed to build fake-engine");
+
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .filter_map(|l| json::from_str::<Value>(l).ok())
+            .find(|msg| msg["target"]["name"] == "fake-engine" && msg["executable"].is_string())
+            .map(|msg| PathBuf::from(msg["executable"].as_str().unwrap()))
+            .expect("executable of fake-engine not found")
+    })
+}
+
+/// A directory laid out like a real installation of both engines.
+struct Engines {
+    dir: TempDir,
+}
+
+impl Engines {
+    /// `config` goes into the `[fake]` table of Mortal's config.toml and the
+    /// `"fake"` object of akochan's tactics.json.
+    fn new(config: &[(&str, Value)]) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let akochan_dir = dir.path().join("akochan");
+        fs::create_dir(&akochan_dir).unwrap();
+        fs::copy(fake_engine(), akochan_dir.join("system.exe")).unwrap();
+
+        let mut toml = String::from("[fake]\n");
+        let mut fake = json::Map::new();
+        for (k, v) in config {
+            toml.push_str(&format!("{k} = {v}\n"));
+            fake.insert((*k).to_owned(), v.clone());
+        }
+        fs::write(dir.path().join("config.toml"), toml).unwrap();
+        let tactics = json::json!({ "tactics": { "jun_pt": [90, 45, 0, -135] }, "fake": fake });
+        fs::write(akochan_dir.join("tactics.json"), tactics.to_string()).unwrap();
+
+        Self { dir }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    /// Writes the HTML report to stdout.
+    fn reporter(&self, engine: &str, player_id: u8) -> Command {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_mjai-reviewer"));
+        cmd.args(["-e", engine, "-a", &player_id.to_string(), "-i", "-"])
+            .args(["-o", "-", "--no-open"])
+            .arg("--cache-dir")
+            .arg(self.path("cache"))
+            .arg("--mortal-exe")
+            .arg(fake_engine())
+            .arg("--mortal-cfg")
+            .arg(self.path("config.toml"))
+            .arg("--akochan-dir")
+            .arg(self.path("akochan"))
+            .arg("--akochan-tactics")
+            .arg(self.path("akochan/tactics.json"));
+        cmd
+    }
+
+    /// Writes the JSON output to stdout.
+    fn reviewer(&self, engine: &str, player_id: u8) -> Command {
+        let mut cmd = self.reporter(engine, player_id);
+        cmd.arg("--json");
+        cmd
+    }
+
+    /// Runs a review that is expected to succeed and returns the review part
+    /// of the output.
+    fn review_json(&self, engine: &str, player_id: u8, log: &str, args: &[&str]) -> Value {
+        let mut cmd = self.reviewer(engine, player_id);
+        cmd.args(args);
+        review(cmd, log)
+    }
+}
+
+fn run(mut cmd: Command, log: &str) -> Output {
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(log.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// Runs a review that is expected to succeed and returns the whole output.
+fn review_view(cmd: Command, log: &str) -> Value {
+    let output = run(cmd, log);
+    assert!(
+        output.status.success(),
+        "review failed: {}",
+        String::from_utf8_lossy(&output.stderr),
+    );
+    json::from_slice(&output.stdout).unwrap()
+}
+
+/// Like `review_view`, but returns only the review part of the output.
+fn review(cmd: Command, log: &str) -> Value {
+    review_view(cmd, log)["review"].take()
+}
+
+fn testdata(desc: &str) -> &'static str {
+    TESTDATA.iter().find(|c| c.desc == desc).unwrap().data
+}
+
+/// All entries of all kyokus of a review.
+fn entries(review: &Value) -> impl Iterator<Item = &Value> {
+    review["kyokus"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .flat_map(|k| k["entries"].as_array().unwrap())
+}
+
+/// Counts the `type`s of the actual actions of all entries.
+fn actual_types(review: &Value) -> Vec<String> {
+    entries(review)
+        .map(|entry| {
+            let actual = &entry["actual"];
+            // akochan gives an array of events
+            let ev = actual.get(0).unwrap_or(actual);
+            ev["type"].as_str().unwrap().to_owned()
+        })
+        .collect()
+}
+
+/// Reviews every testdata log with both engines, at every seat if
+/// `all_seats`, otherwise at one seat each in turn.
+fn review_testdata(all_seats: bool) {
+    let engines = Engines::new(&[]);
+    for (i, &TestCase { desc, data }) in TESTDATA.iter().enumerate() {
+        let log = Log::from_json_str(data).unwrap();
+        let seat = i as u8 % 4;
+        let seats = if all_seats { 0..4 } else { seat..seat + 1 };
+        for player_id in seats {
+            if log.game_length == GameLength::Hanchan {
+                let r = engines.review_json("mortal", player_id, data, &["--no-cache"]);
+                assert_eq!(r["model_tag"], "fake", "case: {desc}");
+            }
+            engines.review_json("akochan", player_id, data, &["--no-cache"]);
+        }
+    }
+}
+
+#[test]
+fn all_testdata() {
+    review_testdata(false);
+}
+
+#[test]
+#[ignore = "slow, run with --ignored"]
+fn all_testdata_all_seats() {
+    review_testdata(true);
+}
+
+#[test]
+fn mortal_nakis_and_kans() {
+    let engines = Engines::new(&[]);
+
+    // player 2 makes all the four kans, with the kan select of Mortal
+    let r = engines.review_json("mortal", 2, testdata("suukantsu_0"), &[]);
+    let actual = actual_types(&r);
+    for t in ["pon", "daiminkan", "kakan", "ankan", "none", "dahai"] {
+        assert!(actual.iter().any(|a| a == t), "no {t} in {actual:?}");
+    }
+    assert!(r.to_string().contains(r#""type":"ankan""#));
+    // kans among the candidates are reconstructed exactly
+    let mut kans = 0;
+    for entry in entries(&r) {
+        let actual = &entry["actual"];
+        if !["daiminkan", "kakan", "ankan"].contains(&actual["type"].as_str().unwrap()) {
+            continue;
+        }
+        kans += 1;
+        let details = entry["details"].as_array().unwrap();
+        assert!(
+            details.iter().any(|d| &d["action"] == actual),
+            "{actual} not in {details:?}",
+        );
+        assert_eq!(entry["is_equal"], entry["expected"] == *actual);
+    }
+    assert_eq!(kans, 4);
+
+    let r = engines.review_json("mortal", 1, testdata("suukantsu_0"), &[]);
+    assert!(actual_types(&r).iter().any(|a| a == "chi"));
+}
+
+#[test]
+fn board() {
+    let engines = Engines::new(&[]);
+    for engine in ["mortal", "akochan"] {
+        let r = engines.review_json(engine, 2, testdata("suukantsu_0"), &[]);
+        let entries: Vec<_> = entries(&r).collect();
+        for entry in &entries {
+            let board = &entry["state"]["board"];
+            assert_eq!(board["tiles_left"], entry["tiles_left"], "{engine}");
+            let seats = board["seats"].as_array().unwrap();
+            assert_eq!(seats.len(), 4);
+            let kans = seats[2]["fuuros"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter(|f| f["type"].as_str().unwrap().ends_with("kan"))
+                .count();
+            let dora_markers = board["dora_markers"].as_array().unwrap().len();
+            // the dora of a kan may be revealed after the next discard
+            assert!(dora_markers == kans + 1 || dora_markers == kans, "{engine}");
+        }
+        let last = &entries.last().unwrap()["state"]["board"];
+        let called = last["seats"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .flat_map(|s| s["river"].as_array().unwrap())
+            .filter(|d| d["called"] == true)
+            .count();
+        assert!(called > 0, "{engine}");
+    }
+}
+
+#[test]
+fn hindsight() {
+    let engines = Engines::new(&[]);
+    let log = testdata("double_ron");
+    let mut deal_ins = 0;
+    for player_id in 0..4 {
+        let r = engines.review_json("mortal", player_id, log, &[]);
+        for entry in entries(&r) {
+            let h = &entry["hindsight"];
+            let opponents = h["opponents"].as_array().unwrap();
+            assert_eq!(opponents.len(), 3);
+            assert!(opponents.iter().all(|o| o["seat"] != player_id));
+            assert!(h["next_draws"].as_array().unwrap().len() <= 3);
+            let deal_in_to = h["deal_in_to"].as_array().unwrap();
+            if !deal_in_to.is_empty() {
+                assert!(["dahai", "reach"].contains(&entry["actual"]["type"].as_str().unwrap()));
+                deal_ins += 1;
+            }
+        }
+    }
+    assert!(deal_ins > 0);
+
+    let mut cmd = engines.reporter("mortal", 3);
+    cmd.args(["--lang", "zh"]);
+    let html = String::from_utf8(run(cmd, log).stdout).unwrap();
+    assert!(html.contains("此打牌会放铳给"));
+    assert!(html.contains("2向听"));
+}
+
+#[test]
+fn safety() {
+    let engines = Engines::new(&[]);
+    let log = testdata("double_ron");
+    let mut analyzed = 0;
+    for player_id in 0..4 {
+        let r = engines.review_json("akochan", player_id, log, &[]);
+        for entry in entries(&r) {
+            let seats = entry["state"]["board"]["seats"].as_array().unwrap();
+            let in_riichi: Vec<_> = (0..4)
+                .filter(|&s| s != player_id && !seats[s as usize]["riichi_at"].is_null())
+                .collect();
+            let safety = &entry["safety"];
+            if in_riichi.is_empty() {
+                assert!(safety.is_null());
+                continue;
+            }
+            analyzed += 1;
+            let threats = safety["threats"].as_array().unwrap();
+            let threat_seats: Vec<_> = threats
+                .iter()
+                .map(|t| t["seat"].as_u64().unwrap() as u8)
+                .collect();
+            assert_eq!(threat_seats, in_riichi);
+            for threat in threats {
+                assert!(!threat["tiles"].as_array().unwrap().is_empty());
+                if safety["actual"].is_string() {
+                    assert!(threat["actual"]["type"].is_string());
+                }
+            }
+        }
+    }
+    assert!(analyzed > 0);
+
+    let mut cmd = engines.reporter("akochan", 3);
+    cmd.args(["--lang", "ja"]);
+    let html = String::from_utf8(run(cmd, log).stdout).unwrap();
+    assert!(html.contains("下家に対して:"));
+    assert!(html.contains("ノーチャンス"));
+}
+
+#[test]
+fn multiple_ron_and_kyushukyuhai() {
+    let engines = Engines::new(&[]);
+    let log = testdata("double_ron");
+    for engine in ["mortal", "akochan"] {
+        for player_id in [0, 2] {
+            let r = engines.review_json(engine, player_id, log, &[]);
+            assert!(
+                actual_types(&r).iter().any(|a| a == "hora"),
+                "{engine} {player_id}",
+            );
+            let horas = r["kyokus"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|k| k["end_status"].as_array().unwrap().len())
+                .max();
+            assert_eq!(horas, Some(2));
+        }
+    }
+
+    let r = engines.review_json("mortal", 0, testdata("kyushukyuhai"), &[]);
+    assert_eq!(actual_types(&r), ["ryukyoku"]);
+}
+
+#[test]
+fn deterministic_and_configurable() {
+    let log = testdata("ranked_game");
+    let a = Engines::new(&[("seed", 1.into()), ("model_tag", "a".into())]);
+    let b = Engines::new(&[("seed", 1.into()), ("model_tag", "a".into())]);
+    let c = Engines::new(&[("seed", 2.into()), ("model_tag", "c".into())]);
+    for engine in ["mortal", "akochan"] {
+        let ra = a.review_json(engine, 3, log, &[]);
+        let rb = b.review_json(engine, 3, log, &[]);
+        let rc = c.review_json(engine, 3, log, &[]);
+        assert_eq!(ra, rb);
+        assert_ne!(ra["kyokus"], rc["kyokus"]);
+    }
+    assert_eq!(c.review_json("mortal", 3, log, &[])["model_tag"], "c");
+}
+
+#[test]
+fn cache_and_transcripts() {
+    let engines = Engines::new(&[]);
+    let log = testdata("double_kakan_then_chankan");
+    for engine in ["mortal", "akochan"] {
+        let transcript = engines.path(&format!("{engine}.transcript"));
+        let mut cmd = engines.reviewer(engine, 1);
+        cmd.arg("--record-transcript").arg(&transcript);
+        let recorded = review(cmd, log);
+
+        // a cache hit
+        let output = run(engines.reviewer(engine, 1), log);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("using cached engine output"));
+        let mut view: Value = json::from_slice(&output.stdout).unwrap();
+        assert_eq!(view["review"].take(), recorded);
+
+        // no engine at all
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_mjai-reviewer"));
+        cmd.args(["-e", engine, "-a", "1", "-i", "-", "--json", "-o", "-"])
+            .args([
+                "--mortal-exe",
+                "/nonexistent",
+                "--akochan-dir",
+                "/nonexistent",
+            ])
+            .arg("--replay-transcript")
+            .arg(&transcript);
+        assert_eq!(review(cmd, log), recorded);
+    }
+}
+
+#[test]
+fn cache_keyed_by_model() {
+    let engines = Engines::new(&[]);
+    let log = testdata("double_kakan_then_chankan");
+    let model = engines.path("mortal.pth");
+    fs::write(&model, "model a").unwrap();
+    let mut config = fs::read_to_string(engines.path("config.toml")).unwrap();
+    config.push_str(&format!(
+        "[control]\nstate_file = {:?}\n",
+        model.display().to_string()
+    ));
+    fs::write(engines.path("config.toml"), config).unwrap();
+
+    let cache_hit = || {
+        let output = run(engines.reviewer("mortal", 1), log);
+        String::from_utf8_lossy(&output.stderr).contains("using cached engine output")
+    };
+    assert!(!cache_hit());
+    assert!(cache_hit());
+
+    // same config.toml, different model
+    fs::write(&model, "model bb").unwrap();
+    assert!(!cache_hit());
+    assert!(cache_hit());
+}
+
+#[test]
+fn engine_faults() {
+    let log = testdata("rinshan");
+
+    let engines = Engines::new(&[("crash_after", 30.into())]);
+    let mut cmd = engines.reviewer("mortal", 0);
+    cmd.args(["--no-cache", "--engine-retries", "1"]);
+    let output = run(cmd, log);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("crashing as configured"), "{stderr}");
+    assert!(stderr.contains("restarting engine"), "{stderr}");
+
+    // crashes once, then gets replayed to where it was after the restart
+    for engine in ["mortal", "akochan"] {
+        let marker = tempfile::tempdir().unwrap();
+        let marker = marker.path().join("crashed");
+        let engines = Engines::new(&[
+            ("crash_after", 30.into()),
+            ("crash_once", marker.display().to_string().into()),
+        ]);
+        let mut cmd = engines.reviewer(engine, 0);
+        cmd.args(["--no-cache", "--engine-retries", "1"]);
+        let output = run(cmd, log);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success(), "{stderr}");
+        assert!(stderr.contains("restarting engine"), "{stderr}");
+        let mut view: Value = json::from_slice(&output.stdout).unwrap();
+
+        let engines = Engines::new(&[]);
+        let mut cmd = engines.reviewer(engine, 0);
+        cmd.arg("--no-cache");
+        assert_eq!(view["review"].take(), review(cmd, log), "engine: {engine}");
+    }
+
+    let engines = Engines::new(&[("hang_after", 30.into())]);
+    let mut cmd = engines.reviewer("akochan", 0);
+    cmd.args(["--no-cache", "--engine-timeout", "1s"]);
+    let output = run(cmd, log);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("did not respond within 1s"), "{stderr}");
+}
+
+#[test]
+fn engine_log() {
+    let log = testdata("double_ron");
+    let engines = Engines::new(&[("verbose", true.into())]);
+    for engine in ["mortal", "akochan"] {
+        let diagnostics = engines.path(&format!("{engine}.diagnostics"));
+        let mut cmd = engines.reviewer(engine, 0);
+        cmd.arg("--no-cache").arg("--diagnostics").arg(&diagnostics);
+        let output = run(cmd, log);
+        assert!(output.status.success());
+        // not mixed with our logs
+        assert!(!String::from_utf8_lossy(&output.stderr).contains("fake-engine: line"));
+        let view: Value = json::from_slice(&output.stdout).unwrap();
+        assert!(view["engine_log"].is_null());
+
+        // stderr is read asynchronously, so a line may be tagged with a later
+        // event than the one that caused it, but never an earlier one
+        let content = fs::read_to_string(&diagnostics).unwrap();
+        let tags: Vec<(usize, usize)> = content
+            .lines()
+            .map(|l| {
+                let (event, line) = l.split_once("\tfake-engine: line #").unwrap();
+                (event[1..].parse().unwrap(), line.parse().unwrap())
+            })
+            .collect();
+        assert!(tags.len() > 10);
+        assert!(tags.windows(2).all(|w| w[0].0 <= w[1].0), "{tags:?}");
+        assert!(
+            tags.iter().all(|&(event, line)| event + 1 >= line),
+            "{tags:?}"
+        );
+
+        let mut cmd = engines.reviewer(engine, 0);
+        cmd.args(["--no-cache", "--verbose"]);
+        let view = review_view(cmd, log);
+        let engine_log = view["engine_log"]["lines"].as_array().unwrap();
+        assert_eq!(engine_log.len(), tags.len());
+        assert!(engine_log[2]["event"].as_u64().unwrap() >= 2);
+        assert_eq!(engine_log[2]["line"], "fake-engine: line #3");
+
+        let mut cmd = engines.reporter(engine, 0);
+        cmd.args(["--no-cache", "--verbose", "--lang", "ko"]);
+        let html = String::from_utf8(run(cmd, log).stdout).unwrap();
+        assert!(html.contains("엔진 로그"), "{engine}");
+    }
+}
+
+#[test]
+fn position() {
+    let engines = Engines::new(&[]);
+    let position = |args: &[&str]| {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_mjai-reviewer"));
+        cmd.args(["position", "--json", "--mortal-exe"])
+            .arg(fake_engine())
+            .arg("--mortal-cfg")
+            .arg(engines.path("config.toml"))
+            .args(args);
+        run(cmd, "")
+    };
+
+    let output = position(&[
+        "-a",
+        "2",
+        "--round",
+        "S3",
+        "--hand",
+        "23m4p1s",
+        "--meld",
+        "chi:546m",
+        "--meld",
+        "ankan:7777z",
+        "--meld",
+        "pon:111z",
+        "--tsumo",
+        "1s",
+        "--dora",
+        "1m2p",
+        "--discards",
+        "19m9p4z",
+    ]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let evaluation: Value = json::from_slice(&output.stdout).unwrap();
+    let details = evaluation["details"].as_array().unwrap();
+    assert_eq!(details.len(), 4);
+    assert_eq!(evaluation["expected"], details[0]["action"]);
+    let sum: f64 = details.iter().map(|d| d["prob"].as_f64().unwrap()).sum();
+    assert!((sum - 1.).abs() < 1e-3);
+
+    // every draw, the one after a kan included, takes a tile out of the wall
+    let mjai_log = engines.path("position.mjson");
+    let output = Command::new(env!("CARGO_BIN_EXE_mjai-reviewer"))
+        .args([
+            "position", "-a", "2", "--hand", "23m4p1s", "--meld", "chi:546m",
+        ])
+        .args([
+            "--meld",
+            "ankan:7777z",
+            "--meld",
+            "kan:1111z",
+            "--tsumo",
+            "1s",
+        ])
+        .args(["--dora", "1m2p3s", "--discards", "19m9p4z", "--mortal-exe"])
+        .arg(fake_engine())
+        .arg("--mortal-cfg")
+        .arg(engines.path("config.toml"))
+        .arg("--mjai-out")
+        .arg(&mjai_log)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let tsumos = fs::read_to_string(&mjai_log)
+        .unwrap()
+        .matches(r#""type":"tsumo""#)
+        .count();
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        text.contains(&format!(", {} tiles left\n", 70 - tsumos)),
+        "{text}"
+    );
+
+    let output = position(&[
+        "-a",
+        "1",
+        "--hand",
+        "23m406p789s11z",
+        "--meld",
+        "pon:555z",
+        "--discarded",
+        "0:1z",
+        "--dora",
+        "1m",
+        "--discards",
+        "9s",
+    ]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let evaluation: Value = json::from_slice(&output.stdout).unwrap();
+    assert!(evaluation.to_string().contains(r#""type":"pon""#));
+
+    // 14 tiles
+    let output = position(&[
+        "--hand",
+        "123456789m12345p",
+        "--tsumo",
+        "1z",
+        "--dora",
+        "1m",
+    ]);
+    assert!(!output.status.success());
+
+    // the engine options apply as well
+    let engines = Engines::new(&[("hang_after", 1.into())]);
+    let output = Command::new(env!("CARGO_BIN_EXE_mjai-reviewer"))
+        .args(["position", "--hand", "123456789m1234p", "--tsumo", "1z"])
+        .args(["--dora", "1m", "--engine-timeout", "1s", "--mortal-exe"])
+        .arg(fake_engine())
+        .arg("--mortal-cfg")
+        .arg(engines.path("config.toml"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("did not respond within 1s"), "{stderr}");
+}
+
+#[test]
+fn metrics() {
+    let engines = Engines::new(&[]);
+    let log = testdata("ranked_game");
+
+    let r = engines.review_json("mortal", 0, log, &[]);
+    assert!(r["rating"].is_null() && r["weighted_mistakes"].is_null());
+    assert_eq!(r["top_k_agreement"].as_array().unwrap().len(), 3);
+    // the totals add up the losses shown per entry
+    let sum: f64 = entries(&r).map(|e| e["loss"].as_f64().unwrap()).sum();
+    assert!((r["total_loss"].as_f64().unwrap() - sum).abs() < 1e-6);
+
+    let r = engines.review_json(
+        "mortal",
+        0,
+        log,
+        &["--metrics", "prob,mistakes", "--show-rating"],
+    );
+    for key in ["rating", "mean_actual_prob", "weighted_mistakes"] {
+        assert!(r[key].is_number(), "no {key}");
+    }
+    assert!(r["total_loss"].is_null());
+
+    let r = engines.review_json("akochan", 0, log, &["--metrics", "loss,prob"]);
+    assert!(r["mean_loss"].as_f64().unwrap() >= 0.);
+    assert!(r["mean_actual_prob"].is_null());
+}
+
+#[test]
+fn categories() {
+    let engines = Engines::new(&[]);
+    let log = testdata("double_ron");
+    for engine in ["mortal", "akochan"] {
+        let r = engines.review_json(engine, 0, log, &[]);
+        let categories: Vec<_> = r["categories"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| c["category"].as_str().unwrap())
+            .collect();
+        assert_eq!(categories, ["efficiency", "riichi", "call", "agari"]);
+
+        let r = engines.review_json(engine, 0, log, &["--only", "call,agari"]);
+        assert_eq!(r["categories"].as_array().unwrap().len(), 2);
+        assert_eq!(r["total_reviewed"], 5);
+        for entry in entries(&r) {
+            assert!(entry["category"] == "call" || entry["category"] == "agari");
+        }
+    }
+}
+
+#[test]
+fn only_nothing_reviewed() {
+    let engines = Engines::new(&[]);
+    let log = testdata("ranked_game");
+    let args = ["--only", "kyushukyuhai", "--show-rating"];
+    for engine in ["mortal", "akochan"] {
+        let mut cmd = engines.reviewer(engine, 0);
+        cmd.args(args);
+        let output = run(cmd, log);
+        assert!(output.status.success(), "{engine}");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("categories of --only"), "{stderr}");
+        let r = &json::from_slice::<Value>(&output.stdout).unwrap()["review"];
+        assert_eq!(r["total_reviewed"], 0, "{engine}");
+        for key in ["rating", "mean_loss", "mean_actual_prob", "top_k_agreement"] {
+            assert!(r[key].is_null(), "{engine} {key}: {}", r[key]);
+        }
+
+        for format in ["html", "text"] {
+            let mut cmd = engines.reporter(engine, 0);
+            cmd.args(args).args(["--format", format]);
+            let output = run(cmd, log);
+            assert!(output.status.success(), "{engine} {format}");
+            let report = String::from_utf8(output.stdout).unwrap();
+            assert!(!report.contains("NaN"), "{engine} {format}");
+        }
+    }
+}
+
+#[test]
+fn performance() {
+    let log = testdata("ranked_game");
+    let engines = Engines::new(&[]);
+    let r = engines.review_json("mortal", 0, log, &[]);
+    let p = &r["performance"];
+    assert!(p["timed"].as_u64().unwrap() >= r["total_reviewed"].as_u64().unwrap());
+    assert_eq!(p["non_greedy"], 0);
+    assert_eq!(p["max_batch_size"], 1);
+    let percentiles =
+        ["p50", "p90", "p99", "max"].map(|k| p[format!("{k}_eval_time_ns")].as_u64().unwrap());
+    assert!(percentiles[0] >= 1_000_000);
+    assert!(percentiles.is_sorted());
+    assert!(p["total_eval_time_ns"].as_u64().unwrap() >= percentiles[3]);
+
+    for entry in entries(&r) {
+        assert_eq!(entry["is_greedy"], true);
+        assert!(entry["eval_time_ns"].as_u64().unwrap() >= 1_000_000);
+    }
+
+    let engines = Engines::new(&[("sample_every", 5.into())]);
+    let output = run(engines.reviewer("mortal", 0), log);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("were not greedy"), "{stderr}");
+    let r = &json::from_slice::<Value>(&output.stdout).unwrap()["review"];
+    assert!(r["performance"]["non_greedy"].as_u64().unwrap() > 0);
+
+    let mut cmd = engines.reporter("mortal", 0);
+    cmd.args(["--lang", "ja"]);
+    let html = String::from_utf8(run(cmd, log).stdout).unwrap();
+    assert!(html.contains("推論時間 (合計 / p50 / p90 / p99 / 最大)"));
+    assert!(html.contains("非貪欲な判断"));
+}
+
+#[test]
+fn tolerance() {
+    let engines = Engines::new(&[]);
+    let log = testdata("ranked_game");
+
+    let r = engines.review_json("mortal", 0, log, &[]);
+    let total_matches = r["total_matches"].as_u64().unwrap();
+    assert_eq!(r["total_tolerated"], 0);
+    assert!(r["tolerance"].is_null());
+
+    for args in [["--tolerance-q", "0.05"], ["--tolerance-prob", "0.5"]] {
+        let r = engines.review_json("mortal", 0, log, &args);
+        assert_eq!(r["total_matches"], total_matches);
+        let total_tolerated = r["total_tolerated"].as_u64().unwrap();
+        assert!(total_tolerated > 0, "nothing tolerated with {args:?}");
+
+        let entries: Vec<_> = entries(&r).filter(|e| e["is_tolerable"] == true).collect();
+        assert_eq!(entries.len() as u64, total_tolerated);
+        for entry in entries {
+            assert_eq!(entry["is_equal"], false);
+            assert!(entry["severity"].is_null());
+            if args[0] == "--tolerance-q" {
+                assert!(entry["loss"].as_f64().unwrap() <= 0.05);
+            }
+        }
+    }
+
+    // everything is tolerated
+    let r = engines.review_json("mortal", 0, log, &["--tolerance-q", "1000"]);
+    let total_reviewed = r["total_reviewed"].as_u64().unwrap();
+    assert_eq!(
+        r["total_tolerated"].as_u64().unwrap() + total_matches,
+        total_reviewed,
+    );
+    assert!(r["top_mistakes"].as_array().unwrap().is_empty());
+
+    let mut cmd = engines.reporter("mortal", 0);
+    cmd.args(["--tolerance-q", "0.05", "--lang", "ja"]);
+    let html = String::from_utf8(run(cmd, log).stdout).unwrap();
+    assert!(html.contains("Q値の差 ≤ 0.05"));
+}
+
+#[test]
+fn tactics_overrides() {
+    let engines = Engines::new(&[]);
+    let log = testdata("double_ron");
+
+    let r = review_view(engines.reviewer("akochan", 0), log);
+    assert_eq!(r["tactics"], json::json!({ "jun_pt": [90, 45, 0, -135] }));
+
+    let mut cmd = engines.reviewer("akochan", 0);
+    cmd.args(["--tactics-preset", "tenhou-houou"]);
+    let r = review_view(cmd, log);
+    assert_eq!(r["tactics"]["jun_pt"], json::json!([90, 45, 0, -165]));
+
+    let mut cmd = engines.reviewer("akochan", 0);
+    cmd.args([
+        "--tactics-preset",
+        "m-league",
+        "--tactic",
+        "do_kan_ordinary=true",
+    ])
+    .args(["--tactic", "jun_est=ako"]);
+    let r = review_view(cmd, log);
+    assert_eq!(r["tactics"]["jun_pt"], json::json!([50, 10, -10, -30]));
+    assert_eq!(r["tactics"]["do_kan_ordinary"], true);
+    assert_eq!(r["tactics"]["jun_est"], "ako");
+
+    let mut cmd = engines.reviewer("akochan", 0);
+    cmd.args(["--tactics-preset", "m-league", "--jun-pt", "-1,2,-3,4"]);
+    let r = review_view(cmd, log);
+    assert_eq!(r["tactics"]["jun_pt"], json::json!([-1, 2, -3, 4]));
+
+    for args in [["--jun-pt", "1,2,3"], ["--tactic", "jun_est=akoo"]] {
+        let mut cmd = engines.reviewer("akochan", 0);
+        cmd.args(args);
+        assert!(!run(cmd, log).status.success(), "{args:?}");
+    }
+
+    let check = |path: &Path| {
+        Command::new(env!("CARGO_BIN_EXE_mjai-reviewer"))
+            .args(["tactics", "check"])
+            .arg(path)
+            .output()
+            .unwrap()
+    };
+    let output = check(&engines.path("akochan/tactics.json"));
+    assert!(output.status.success());
+    let bad = engines.path("bad.json");
+    fs::write(
+        &bad,
+        r#"{ "tactics": { "jun_pt": [90, 45, 0, -135], "do_kan": true } }"#,
+    )
+    .unwrap();
+    let output = check(&bad);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("do_kan"));
+}
+
+#[test]
+fn defense() {
+    let engines = Engines::new(&[]);
+    // player 3 deals in to both
+    let r = engines.review_json("akochan", 3, testdata("double_ron"), &[]);
+    let defense = &r["defense"];
+    assert!(defense["decisions"].as_u64().unwrap() > 0);
+    assert!(defense["total_risk_taken"].as_f64() >= defense["total_risk_necessary"].as_f64());
+    assert_eq!(defense["deal_ins"], 1);
+
+    let kyoku = &r["kyokus"][0];
+    let pushed = kyoku["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|e| e["defense"]["is_high_risk"] == true);
+    assert_eq!(kyoku["risky_deal_in"], pushed);
+    for entry in kyoku["entries"].as_array().unwrap() {
+        let d = &entry["defense"];
+        if !d.is_null() {
+            assert!(d["safest"]["prob"].as_f64() <= d["actual"]["prob"].as_f64());
+        }
+    }
+
+    let r = engines.review_json("akochan", 0, testdata("double_ron"), &[]);
+    assert_eq!(r["defense"]["deal_ins"], 0);
+    assert_eq!(r["kyokus"][0]["risky_deal_in"], false);
+
+    let mut cmd = engines.reporter("akochan", 3);
+    cmd.args(["--lang", "zh"]);
+    let html = String::from_utf8(run(cmd, testdata("double_ron")).stdout).unwrap();
+    assert!(html.contains("放铳风险 实际 / 必要 (平均)"));
+}
+
+#[test]
+fn top_mistakes() {
+    let engines = Engines::new(&[]);
+    let log = testdata("ranked_game");
+    for engine in ["mortal", "akochan"] {
+        let r = engines.review_json(engine, 0, log, &[]);
+        let top = r["top_mistakes"].as_array().unwrap();
+        assert_eq!(top.len(), 5);
+        let losses: Vec<_> = top.iter().map(|m| m["loss"].as_f64().unwrap()).collect();
+        assert!(losses.windows(2).all(|w| w[0] >= w[1]), "{losses:?}");
+        for m in top {
+            let k = m["kyoku_index"].as_u64().unwrap() as usize;
+            let e = m["entry_index"].as_u64().unwrap() as usize;
+            let entry = &r["kyokus"][k]["entries"][e];
+            assert_eq!(entry["severity"], m["severity"]);
+            assert_eq!(entry["loss"], m["loss"]);
+        }
+
+        // everything is a blunder
+        let r = engines.review_json(
+            engine,
+            0,
+            log,
+            &["--blunder-threshold", "0", "--top-mistakes", "2"],
+        );
+        let top = r["top_mistakes"].as_array().unwrap();
+        assert_eq!(top.len(), 2);
+        assert!(top.iter().all(|m| m["severity"] == "blunder"));
+    }
+}
+
+#[test]
+fn offline_report() {
+    let engines = Engines::new(&[]);
+    for engine in ["mortal", "akochan"] {
+        let mut cmd = engines.reporter(engine, 0);
+        cmd.arg("--offline");
+        let output = run(cmd, testdata("ranked_game"));
+        assert!(output.status.success(), "{engine}");
+        let html = String::from_utf8(output.stdout).unwrap();
+        // links are fine as they are only followed on click
+        for needle in ["src=\"http", "href=\"https://unpkg.com", "<iframe"] {
+            assert!(!html.contains(needle), "{engine}: {needle}");
+        }
+        assert!(html.contains("class=\"board"), "{engine}");
+
+        let output = run(engines.reporter(engine, 0), testdata("ranked_game"));
+        let html = String::from_utf8(output.stdout).unwrap();
+        assert!(html.contains("unpkg.com/katex"), "{engine}");
+    }
+}
+
+#[test]
+fn replayer() {
+    let engines = Engines::new(&[]);
+    let log = testdata("ranked_game");
+    let view = review_view(engines.reviewer("mortal", 0), log);
+    let events = view["mjai_log"].as_array().unwrap();
+    let kyokus = view["review"]["kyokus"].as_array().unwrap();
+
+    let output = run(engines.reporter("mortal", 0), log);
+    let html = String::from_utf8(output.stdout).unwrap();
+    // the boards of each kyoku are embedded as JSON
+    let replays: Vec<Value> = html
+        .split("<script type=application/json>")
+        .skip(1)
+        .map(|s| json::from_str(&s[..s.find("</script>").unwrap()]).unwrap())
+        .collect();
+    assert_eq!(replays.len(), kyokus.len());
+    for (replay, kyoku) in replays.iter().zip(kyokus) {
+        let first = replay["first"].as_u64().unwrap() as usize;
+        let boards = replay["boards"].as_array().unwrap();
+        assert_eq!(replay["events"].as_array().unwrap().len(), boards.len());
+        assert_eq!(events[first]["type"], "start_kyoku");
+        assert_eq!(events[first + boards.len() - 1]["type"], "end_kyoku");
+        // every hand is known in this log
+        assert_eq!(boards[0]["seats"][3]["tehai"].as_array().unwrap().len(), 13);
+
+        for entry in kyoku["entries"].as_array().unwrap() {
+            let index = entry["event_index"].as_u64().unwrap() as usize;
+            let board = &boards[index - first];
+            assert_eq!(board["tiles_left"], entry["tiles_left"]);
+            assert_eq!(
+                board["seats"][0]["river"],
+                entry["state"]["board"]["seats"][0]["river"]
+            );
+        }
+    }
+    assert_eq!(
+        html.matches("class=replayer-jump").count(),
+        entries(&view["review"]).count(),
+    );
+
+    let mut cmd = engines.reporter("mortal", 0);
+    cmd.arg("--without-log-viewer");
+    let html = String::from_utf8(run(cmd, log).stdout).unwrap();
+    assert!(!html.contains("<script type=application/json>"));
+}
+
+#[test]
+fn text_reports() {
+    let engines = Engines::new(&[]);
+    let log = testdata("ranked_game");
+    for engine in ["mortal", "akochan"] {
+        let view = review_view(engines.reviewer(engine, 0), log);
+        let entry_count = entries(&view["review"]).count();
+
+        for format in ["text", "markdown"] {
+            let mut cmd = engines.reporter(engine, 0);
+            cmd.args(["--format", format]);
+            let output = run(cmd, log);
+            assert!(output.status.success(), "{engine} {format}");
+            let text = String::from_utf8(output.stdout).unwrap();
+            let turns = if format == "markdown" {
+                "### Turn "
+            } else {
+                "* Turn "
+            };
+            assert_eq!(
+                text.matches(turns).count(),
+                entry_count,
+                "{engine} {format}"
+            );
+            assert!(!text.contains("<"), "{engine} {format}");
+            // hands in compact notation
+            assert!(
+                text.lines()
+                    .any(|l| l.starts_with("- ") && l.contains("z Draw ")),
+                "{engine} {format}",
+            );
+        }
+    }
+
+    let mut cmd = engines.reporter("mortal", 0);
+    cmd.args(["--format", "markdown", "--lang", "ja"]);
+    let text = String::from_utf8(run(cmd, log).stdout).unwrap();
+    assert!(text.starts_with("# 牌譜検討\n"));
+    assert!(text.contains("| 1 | 打 "));
+    assert!(text.contains("\n- Q値損失 (合計 / 平均): "));
+    assert!(text.contains("\n- 牌効率"));
+}
+
+#[test]
+fn csv_export() {
+    let engines = Engines::new(&[]);
+    let log = testdata("ranked_game");
+    for engine in ["mortal", "akochan"] {
+        let view = review_view(engines.reviewer(engine, 1), log);
+        let entry_count = entries(&view["review"]).count();
+
+        let mut cmd = engines.reporter(engine, 1);
+        cmd.args(["--format", "csv"]);
+        let output = run(cmd, log);
+        assert!(output.status.success(), "{engine}");
+        let text = String::from_utf8(output.stdout).unwrap();
+        let mut lines = text.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "game_id,seat,kyoku,honba,junme,tiles_left,category,severity,shanten,at_furiten,\
+             actual,expected,is_equal,is_tolerable,actual_value,expected_value,loss",
+        );
+        let rows: Vec<_> = lines.collect();
+        assert_eq!(rows.len(), entry_count, "{engine}");
+        assert!(rows.iter().all(|r| r.starts_with(",1,")), "{engine}");
+        assert!(rows.iter().any(|r| r.contains(",dahai ")), "{engine}");
+    }
+
+    // --json is a shorthand of --format json and does not override it
+    let mut cmd = engines.reviewer("mortal", 1);
+    cmd.args(["--format", "csv"]);
+    let output = run(cmd, log);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be used with"));
+}
+
+#[test]
+fn render_saved() {
+    let engines = Engines::new(&[]);
+    let log = testdata("double_ron");
+    let saved = engines.path("review.json");
+    let render = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_mjai-reviewer"))
+            .arg("render")
+            .arg(&saved)
+            .args(["-o", "-", "--no-open"])
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    for engine in ["mortal", "akochan"] {
+        let mut cmd = engines.reviewer(engine, 0);
+        cmd.arg("--show-rating");
+        let output = run(cmd, log);
+        assert!(output.status.success(), "{engine}");
+        fs::write(&saved, &output.stdout).unwrap();
+        let view: Value = json::from_slice(&output.stdout).unwrap();
+        assert_eq!(view["format_version"], 1, "{engine}");
+
+        // read back without loss
+        let output = render(&["--format", "json"]);
+        assert!(output.status.success(), "{engine}");
+        assert_eq!(output.stdout, fs::read(&saved).unwrap(), "{engine}");
+
+        let output = render(&["--lang", "ja", "--layout", "horizontal", "--show-rating"]);
+        assert!(output.status.success(), "{engine}");
+        let html = String::from_utf8(output.stdout).unwrap();
+        assert!(html.contains("<html lang=ja"), "{engine}");
+        assert!(html.contains("<body data-horizontal>"), "{engine}");
+        assert!(html.contains("福地誠"), "{engine}");
+        assert!(html.contains("損失 (合計 / 平均)"), "{engine}");
+        assert!(html.contains("<th>一致率 (%)"), "{engine}");
+        assert!(html.contains("ワーストミス"), "{engine}");
+        assert!(html.contains("、供託 0、残り"), "{engine}");
+
+        let html = String::from_utf8(render(&["--anonymous"]).stdout).unwrap();
+        assert!(html.contains("<body>"), "{engine}");
+        assert!(!html.contains("福地誠"), "{engine}");
+
+        let entry_count = entries(&view["review"]).count();
+        let csv = String::from_utf8(render(&["--format", "csv"]).stdout).unwrap();
+        assert_eq!(csv.lines().count(), entry_count + 1, "{engine}");
+    }
+
+    // rating is left out of the JSON without --show-rating
+    let output = run(engines.reviewer("mortal", 0), log);
+    fs::write(&saved, &output.stdout).unwrap();
+    let output = render(&["--show-rating"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--show-rating"));
+
+    fs::write(&saved, "{}").unwrap();
+    let output = render(&[]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("format version"));
+}